@@ -0,0 +1,257 @@
+use chip8::audio::AudioConfig;
+use chip8::cpu::{ErrorPolicy, FaultAction};
+use chip8::frontend::Timing;
+use chip8::profile::ProfileFormat;
+use chip8::quirks::Platform;
+use chip8::trace::TraceFilter;
+
+pub const USAGE: &str =
+    "usage: chip8 [--sdl | --tui] [--platform NAME] [--clock HZ] [--timer-hz HZ]
+             [--on-fault [KIND=]ACTION]... [--beep-hz HZ] [--volume PERCENT]
+             [--waveform NAME] [--mute] [--wav FILE] [--rewind SECONDS]
+             [--seed N] [--record FILE | --play FILE]
+             [--trace FILE [--trace-range START-END]... [--trace-only NAMES]]
+             [--profile FILE [--profile-format NAME] [--profile-top N]]
+             [--flamegraph FILE] [--callgraph FILE]
+             [--coverage FILE] [--coverage-dump FILE]
+             [--debug] ROM
+       chip8 --dap [OPTIONS]
+       chip8 disasm ROM
+       chip8 asm SOURCE [-o ROM]
+       chip8 tracediff TRACE TRACE [--context N]
+
+  --platform    quirks to emulate: vip (default), chip48, schip or xochip
+  --clock       instructions per second (default 500)
+  --timer-hz    delay and sound timer rate (default 60)
+  --beep-hz     beeper frequency (default 440)
+  --volume      beeper volume (default 25)
+  --waveform    square (default), triangle, sawtooth or sine
+  --mute        start muted; M toggles sound while running
+  --wav         record the beeper to FILE instead of playing it
+  --seed        seed CXNN's random numbers to make a run reproducible
+  --record      save the keypad input of the run to a movie FILE
+  --play        replay a movie FILE instead of reading the keypad; its platform,
+                clock, seed and fault policy override the options above
+  --debug       step through the ROM at a debugger prompt instead of running it
+  --dap         serve the Debug Adapter Protocol on stdin/stdout; the ROM comes
+                from the editor's launch request
+  --trace       log every instruction and the registers to FILE (- for stderr)
+  --trace-range only trace instructions between two hex addresses, inclusive
+  --trace-only  only trace these comma-separated mnemonics, e.g. DRW,CALL
+  --profile     count executions by address and instruction, and write a report
+                to FILE (- for stdout) when the program exits
+  --profile-format  text (default) or json
+  --profile-top the number of hottest addresses to report (default 20)
+  --flamegraph  write the instructions run under each call stack to FILE as
+                folded stacks, e.g. for flamegraph.pl or inferno
+  --callgraph   write the subroutine call graph to FILE in Graphviz DOT
+  --coverage    write which ROM bytes ran, were read via I or were written
+                to FILE when the program exits
+  --coverage-dump  write a hex dump of the ROM marked up the same way to FILE
+  --rewind      seconds of history to keep for rewinding (default 10, 0 = off)
+  --on-fault    halt, skip or nop on a ROM fault; KIND limits it to one of
+                invalid-opcode, stack-underflow, stack-overflow, pc-out-of-bounds,
+                index-out-of-bounds or invalid-key
+
+While running, F1-F4 save the machine to slots 1-4 (ROM.state1 to ROM.state4)
+and F5-F8 load them back. Holding Backspace plays the game backwards (SDL only).
+
+asm writes ROM (default: SOURCE with a .ch8 extension) and its labels to ROM.sym;
+constants are left out. Its mnemonics are the ones disasm prints.
+
+tracediff reports the first instruction where two --trace logs disagree on PC,
+registers, I or memory writes, with N records of context (default 5). Traces
+from other emulators can be compared too; see the chip8::trace docs.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontendKind {
+    Sdl,
+    Terminal,
+}
+
+impl Default for FrontendKind {
+    #[cfg(feature = "sdl")]
+    fn default() -> FrontendKind {
+        FrontendKind::Sdl
+    }
+
+    #[cfg(not(feature = "sdl"))]
+    fn default() -> FrontendKind {
+        FrontendKind::Terminal
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub rom: String,
+    pub frontend: FrontendKind,
+    pub error_policy: ErrorPolicy,
+    pub platform: Platform,
+    pub timing: Timing,
+    pub audio: AudioConfig,
+    pub wav: Option<String>,
+    pub rewind_seconds: u32,
+    pub seed: Option<u32>,
+    pub record: Option<String>,
+    pub play: Option<String>,
+    pub debug: bool,
+    pub dap: bool,
+    pub trace: Option<String>,
+    pub trace_filter: TraceFilter,
+    pub profile: Option<String>,
+    pub profile_format: ProfileFormat,
+    pub profile_top: u32,
+    pub flamegraph: Option<String>,
+    pub callgraph: Option<String>,
+    pub coverage: Option<String>,
+    pub coverage_dump: Option<String>,
+}
+
+fn parse_fault_action(action: &str) -> Result<FaultAction, String> {
+    match action {
+        "halt" => Ok(FaultAction::Halt),
+        "skip" => Ok(FaultAction::Skip),
+        "nop" => Ok(FaultAction::Nop),
+        _ => Err(format!("unknown fault action {}", action)),
+    }
+}
+
+fn apply_fault_spec(policy: &mut ErrorPolicy, spec: &str) -> Result<(), String> {
+    let (kind, action) = match spec.find('=') {
+        Some(i) => (Some(&spec[..i]), &spec[i + 1..]),
+        None => (None, spec),
+    };
+    let action = parse_fault_action(action)?;
+
+    match kind {
+        None => *policy = ErrorPolicy::all(action),
+        Some("invalid-opcode") => policy.invalid_opcode = action,
+        Some("stack-underflow") => policy.stack_underflow = action,
+        Some("stack-overflow") => policy.stack_overflow = action,
+        Some("pc-out-of-bounds") => policy.pc_out_of_bounds = action,
+        Some("index-out-of-bounds") => policy.index_out_of_bounds = action,
+        Some("invalid-key") => policy.invalid_key = action,
+        Some(kind) => return Err(format!("unknown fault kind {}", kind)),
+    }
+    Ok(())
+}
+
+fn parse_range(range: &str) -> Result<(usize, usize), String> {
+    let bad = || format!("bad address range {}, expected START-END", range);
+    let (start, end) = match range.find('-') {
+        Some(i) => (&range[..i], &range[i + 1..]),
+        None => return Err(bad()),
+    };
+    let hex = |s: &str| usize::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|_| bad());
+    Ok((hex(start)?, hex(end)?))
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn number<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<u32, String> {
+    let value = value(args, flag)?;
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got {}", flag, value))
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut rom = None;
+    let mut frontend = FrontendKind::default();
+    let mut error_policy = ErrorPolicy::default();
+    let mut platform = Platform::default();
+    let mut timing = Timing::default();
+    let mut audio = AudioConfig::default();
+    let mut wav = None;
+    let mut rewind_seconds = 10;
+    let mut seed = None;
+    let mut record = None;
+    let mut play = None;
+    let mut debug = false;
+    let mut dap = false;
+    let mut trace = None;
+    let mut trace_filter = TraceFilter::default();
+    let mut profile = None;
+    let mut profile_format = ProfileFormat::default();
+    let mut profile_top = 20;
+    let mut flamegraph = None;
+    let mut callgraph = None;
+    let mut coverage = None;
+    let mut coverage_dump = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sdl" => frontend = FrontendKind::Sdl,
+            "--tui" => frontend = FrontendKind::Terminal,
+            "--platform" => platform = value(&mut args, &arg)?.parse()?,
+            "--clock" => timing.clock_hz = number(&mut args, &arg)?,
+            "--timer-hz" => timing.timer_hz = number(&mut args, &arg)?,
+            "--beep-hz" => audio.frequency = number(&mut args, &arg)? as f32,
+            "--volume" => audio.volume = number(&mut args, &arg)?.min(100) as f32 / 100.0,
+            "--waveform" => audio.waveform = value(&mut args, &arg)?.parse()?,
+            "--mute" => audio.muted = true,
+            "--wav" => wav = Some(value(&mut args, &arg)?),
+            "--seed" => seed = Some(number(&mut args, &arg)?),
+            "--record" => record = Some(value(&mut args, &arg)?),
+            "--play" => play = Some(value(&mut args, &arg)?),
+            "--debug" => debug = true,
+            "--dap" => dap = true,
+            "--trace" => trace = Some(value(&mut args, &arg)?),
+            "--trace-range" => trace_filter
+                .ranges
+                .push(parse_range(&value(&mut args, &arg)?)?),
+            "--trace-only" => trace_filter.mnemonics.extend(
+                value(&mut args, &arg)?
+                    .split(',')
+                    .filter(|m| !m.is_empty())
+                    .map(str::to_string),
+            ),
+            "--profile" => profile = Some(value(&mut args, &arg)?),
+            "--profile-format" => profile_format = value(&mut args, &arg)?.parse()?,
+            "--profile-top" => profile_top = number(&mut args, &arg)?,
+            "--flamegraph" => flamegraph = Some(value(&mut args, &arg)?),
+            "--callgraph" => callgraph = Some(value(&mut args, &arg)?),
+            "--coverage" => coverage = Some(value(&mut args, &arg)?),
+            "--coverage-dump" => coverage_dump = Some(value(&mut args, &arg)?),
+            "--rewind" => rewind_seconds = number(&mut args, &arg)?,
+            "--on-fault" => apply_fault_spec(&mut error_policy, &value(&mut args, &arg)?)?,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    if record.is_some() && play.is_some() {
+        return Err("--record and --play cannot be used together".to_string());
+    }
+
+    Ok(Options {
+        rom: match rom {
+            Some(rom) => rom,
+            None if dap => String::new(),
+            None => return Err("filename?".to_string()),
+        },
+        frontend,
+        error_policy,
+        platform,
+        timing,
+        audio,
+        wav,
+        rewind_seconds,
+        seed,
+        record,
+        play,
+        debug,
+        dap,
+        trace,
+        trace_filter,
+        profile,
+        profile_format,
+        profile_top,
+        flamegraph,
+        callgraph,
+        coverage,
+        coverage_dump,
+    })
+}
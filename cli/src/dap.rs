@@ -0,0 +1,807 @@
+//! A Debug Adapter Protocol server on stdin/stdout, so editors can launch a
+//! ROM, set breakpoints, step and inspect registers and the call stack.
+//!
+//! ROMs have no source, so the server disassembles the ROM and serves the
+//! listing through a `sourceReference`: stack frames and line breakpoints
+//! use its line numbers. Breakpoints can also be set by address through
+//! instruction breakpoints, and the disassembly view works from `disassemble`
+//! requests.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::iter;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use chip8::cpu::CPU;
+use chip8::debugger::{Debugger, Stop, StopAt};
+use chip8::disasm::{self, Labels, Line};
+use chip8::frontend::{Command, Display, Input, FRAME_RATE};
+use chip8::instructions::Instructions;
+use chip8::rng::Rng;
+use cli::Options;
+
+const THREAD_ID: i64 = 1;
+
+//the sourceReference of the listing, the only source there is
+const LISTING: i64 = 1;
+
+//variablesReference values for the scopes
+const REGISTERS: i64 = 1;
+const TIMERS: i64 = 2;
+
+/// Reads framed messages from stdin on a thread of its own, so the server can
+/// keep the program running while waiting for requests.
+fn spawn_reader() -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        while let Ok(Some(message)) = read_message(&mut stdin) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Reads one `Content-Length` framed JSON message, or None at end of input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0_u8; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The disassembly of the launched ROM, which stands in for its source.
+struct Listing {
+    name: String,
+
+    text: String,
+
+    lines: Vec<(i64, usize)>, //line number and address of each instruction or data word, in order
+}
+
+impl Listing {
+    fn new(program: &str, rom: &[u8]) -> Listing {
+        let listing = disasm::disassemble(rom);
+        //labels take a line of their own before the line they name
+        let mut line = 0;
+        let mut lines = vec![];
+        for l in &listing.lines {
+            if listing.labels.contains_key(&l.address) {
+                line += 1;
+            }
+            line += 1;
+            lines.push((line, l.address));
+        }
+
+        let name = Path::new(program)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Listing {
+            name: format!("{} (disassembly)", name),
+            text: listing.to_string(),
+            lines,
+        }
+    }
+
+    fn source(&self) -> Value {
+        json!({ "name": self.name, "sourceReference": LISTING })
+    }
+
+    /// The line showing the instruction at `addr`, if one starts there.
+    fn line_of(&self, addr: usize) -> Option<i64> {
+        self.lines
+            .binary_search_by_key(&addr, |&(_, a)| a)
+            .ok()
+            .map(|n| self.lines[n].0)
+    }
+
+    /// The first instruction at or after `line`, which moves breakpoints off
+    /// label lines, as its line and address.
+    fn instruction_at(&self, line: i64) -> Option<(i64, usize)> {
+        let n = match self.lines.binary_search_by_key(&line, |&(l, _)| l) {
+            Ok(n) | Err(n) => n,
+        };
+        self.lines.get(n).cloned()
+    }
+}
+
+fn parse_address(s: &str) -> Option<usize> {
+    usize::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+pub struct Server<'a, W: Write> {
+    out: W,
+
+    seq: i64,
+
+    options: &'a Options,
+
+    listing: Option<Listing>, //of the launched ROM
+
+    cpu: Option<CPU>,
+
+    debugger: Debugger,
+
+    line_breakpoints: Vec<usize>,
+
+    instruction_breakpoints: Vec<usize>,
+
+    stop_on_entry: bool,
+
+    running: bool,
+
+    until: Option<StopAt>, //where a running step over/out ends
+
+    cycle_acc: u32,
+}
+
+impl<'a, W: Write> Server<'a, W> {
+    pub fn new(out: W, options: &'a Options) -> Server<'a, W> {
+        Server {
+            out,
+            seq: 0,
+            options,
+            listing: None,
+            cpu: None,
+            debugger: Debugger::new(options.timing),
+            line_breakpoints: vec![],
+            instruction_breakpoints: vec![],
+            stop_on_entry: false,
+            running: false,
+            until: None,
+            cycle_acc: 0,
+        }
+    }
+
+    /// Serves requests from stdin until the client disconnects. While the
+    /// program runs it is shown on `display` and reads keys from `input`.
+    pub fn serve<D, I>(&mut self, display: &mut D, input: &mut I) -> io::Result<()>
+    where
+        D: Display + ?Sized,
+        I: Input + ?Sized,
+    {
+        let requests = spawn_reader();
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut next_frame = Instant::now();
+
+        loop {
+            //block for requests while stopped, only drain them while running
+            let request = if self.running {
+                match requests.try_recv() {
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            } else {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return Ok(()),
+                }
+            };
+            if let Some(request) = request {
+                if !self.handle(&request)? {
+                    return Ok(());
+                }
+                next_frame = Instant::now();
+                continue;
+            }
+
+            if !self.run_frame(display, input)? {
+                return Ok(());
+            }
+            next_frame += frame;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+    }
+
+    /// Runs one frame of a continue or step over/out. Returns false when the
+    /// session is over.
+    fn run_frame<D, I>(&mut self, display: &mut D, input: &mut I) -> io::Result<bool>
+    where
+        D: Display + ?Sized,
+        I: Input + ?Sized,
+    {
+        let cpu = match self.cpu {
+            Some(ref mut cpu) => cpu,
+            None => return Ok(true),
+        };
+
+        for command in input.poll(&mut cpu.keys) {
+            if command == Command::Quit {
+                self.event("terminated", json!({}))?;
+                return Ok(false);
+            }
+        }
+
+        self.cycle_acc += self.options.timing.clock_hz;
+        let count = self.cycle_acc / FRAME_RATE;
+        self.cycle_acc %= FRAME_RATE;
+        let stop = self.debugger.run(cpu, count as u64, self.until);
+        display.present(&cpu.gpu);
+
+        match stop {
+            Stop::Done => Ok(true),
+            Stop::Reached => self.stopped("step", None).map(|_| true),
+            Stop::Breakpoint => self.stopped("breakpoint", None).map(|_| true),
+            Stop::Fault(e) => self.stopped("exception", Some(e.to_string())).map(|_| true),
+            Stop::Exited => {
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                self.event("terminated", json!({}))?;
+                self.running = false;
+                Ok(true)
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        self.running = false;
+        self.until = None;
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["text"] = json!(text);
+        }
+        self.event("stopped", body)
+    }
+
+    /// Continues until `until`, a breakpoint or a fault.
+    fn resume(&mut self, until: Option<StopAt>) {
+        self.debugger.resume();
+        self.running = true;
+        self.until = until;
+    }
+
+    /// Handles one request. Returns false when the client disconnects.
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or("");
+        let args = &request["arguments"];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsDisassembleRequest": true,
+                "supportsSteppingGranularity": true,
+            })),
+            "source" => self.source(args),
+            "launch" => self.launch(args),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.respond(request, Ok(json!({})))?;
+                    self.stopped("entry", None)?;
+                    return Ok(true);
+                }
+                self.resume(None);
+                Ok(json!({}))
+            }
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.with_cpu(stack_trace),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                { "name": "Timers", "variablesReference": TIMERS, "expensive": false },
+            ]})),
+            "variables" => {
+                let reference = args["variablesReference"].as_i64().unwrap_or(0);
+                self.with_cpu(|cpu, _| variables(cpu, reference))
+            }
+            "disassemble" => self.with_cpu(|cpu, listing| disassemble(cpu, listing, args)),
+            "continue" => {
+                self.resume(None);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" => match self.step(command) {
+                //a single step is over at once; report it after the response
+                Ok(Some((reason, text))) => {
+                    self.respond(request, Ok(json!({})))?;
+                    self.stopped(reason, text)?;
+                    return Ok(true);
+                }
+                Ok(None) => Ok(json!({})),
+                Err(e) => Err(e),
+            },
+            "pause" => {
+                self.respond(request, Ok(json!({})))?;
+                self.stopped("pause", None)?;
+                return Ok(true);
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            _ => Err(format!("unsupported request {}", command)),
+        };
+
+        let launched = command == "launch" && result.is_ok();
+        self.respond(request, result)?;
+        if launched {
+            self.event("initialized", json!({}))?;
+        }
+        Ok(true)
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = args["program"].as_str().ok_or("launch needs a program")?;
+        let platform = match args["platform"].as_str() {
+            Some(name) => name.parse()?,
+            None => self.options.platform,
+        };
+        let rom = fs::read(program).map_err(|e| format!("{}: {}", program, e))?;
+        let mut cpu =
+            CPU::with_platform(&rom, platform).map_err(|e| format!("{}: {}", program, e))?;
+
+        cpu.error_policy = self.options.error_policy;
+        cpu.rng = match args["seed"]
+            .as_u64()
+            .map(|s| s as u32)
+            .or(self.options.seed)
+        {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_entropy(),
+        };
+
+        self.listing = Some(Listing::new(program, &rom));
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.cpu = Some(cpu);
+        Ok(json!({}))
+    }
+
+    /// Steps one instruction and returns how it stopped, or starts running
+    /// to the end of a call for a step over or out.
+    fn step(&mut self, command: &str) -> Result<Option<(&'static str, Option<String>)>, String> {
+        let cpu = self.cpu.as_mut().ok_or("no program is running")?;
+        let depth = cpu.stack.len();
+        let calls = is_call(cpu);
+
+        match command {
+            //over a call: run until it returns
+            "next" if calls => self.resume(Some(StopAt::Depth(depth))),
+            "stepOut" if depth > 0 => self.resume(Some(StopAt::Depth(depth - 1))),
+            _ => {
+                self.debugger.resume();
+                let stop = self.debugger.run(cpu, 1, None);
+                return Ok(Some(match stop {
+                    Stop::Fault(e) => ("exception", Some(e.to_string())),
+                    _ => ("step", None),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Answers a `source` request with the listing.
+    fn source(&self, args: &Value) -> Result<Value, String> {
+        let reference = args["sourceReference"]
+            .as_i64()
+            .or_else(|| args["source"]["sourceReference"].as_i64());
+        match self.listing {
+            Some(ref listing) if reference == Some(LISTING) => Ok(json!({
+                "content": listing.text,
+                "mimeType": "text/x-chip8",
+            })),
+            _ => Err("there is no such source".to_string()),
+        }
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let lines: Vec<i64> = args["breakpoints"]
+            .as_array()
+            .map(|bps| bps.iter().filter_map(|bp| bp["line"].as_i64()).collect())
+            .unwrap_or_default();
+        let found: Vec<Option<(i64, usize)>> = lines
+            .iter()
+            .map(|&line| {
+                self.listing
+                    .as_ref()
+                    .and_then(|listing| listing.instruction_at(line))
+            })
+            .collect();
+        self.line_breakpoints = found
+            .iter()
+            .filter_map(|f| f.map(|(_, addr)| addr))
+            .collect();
+        self.sync_breakpoints();
+
+        let verified: Vec<Value> = found
+            .iter()
+            .map(|found| match *found {
+                Some((line, _)) => json!({ "verified": true, "line": line }),
+                None => json!({ "verified": false, "message": "no instruction there" }),
+            })
+            .collect();
+        json!({ "breakpoints": verified })
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        let addrs: Vec<Option<usize>> = args["breakpoints"]
+            .as_array()
+            .map(|bps| {
+                bps.iter()
+                    .map(|bp| {
+                        let base = bp["instructionReference"].as_str().and_then(parse_address);
+                        let offset = bp["offset"].as_i64().unwrap_or(0);
+                        base.and_then(|addr| (addr as i64).checked_add(offset))
+                            .filter(|addr| *addr >= 0)
+                            .map(|addr| addr as usize)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.instruction_breakpoints = addrs.iter().filter_map(|a| *a).collect();
+        self.sync_breakpoints();
+
+        let verified: Vec<Value> = addrs
+            .iter()
+            .map(|addr| match *addr {
+                Some(addr) => json!({
+                    "verified": true,
+                    "instructionReference": format!("0x{:04X}", addr),
+                }),
+                None => json!({ "verified": false, "message": "bad address" }),
+            })
+            .collect();
+        json!({ "breakpoints": verified })
+    }
+
+    fn sync_breakpoints(&mut self) {
+        self.debugger.breakpoints = self
+            .line_breakpoints
+            .iter()
+            .chain(self.instruction_breakpoints.iter())
+            .cloned()
+            .collect();
+    }
+
+    fn with_cpu<F>(&self, f: F) -> Result<Value, String>
+    where
+        F: FnOnce(&CPU, &Listing) -> Value,
+    {
+        match (self.cpu.as_ref(), self.listing.as_ref()) {
+            (Some(cpu), Some(listing)) => Ok(f(cpu, listing)),
+            _ => Err("no program is running".to_string()),
+        }
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+        });
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+                response["body"] = body;
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.out.flush()
+    }
+}
+
+fn opcode_at(cpu: &CPU, addr: usize) -> Option<u16> {
+    cpu.mem
+        .get_ref()
+        .get(addr..addr + 2)
+        .map(|w| (w[0] as u16) << 8 | w[1] as u16)
+}
+
+fn is_call(cpu: &CPU) -> bool {
+    matches!(
+        opcode_at(cpu, cpu.pc).map(|op| Instructions::decode(op, cpu.pc)),
+        Some(Ok(Instructions::CallSub(_)))
+    )
+}
+
+fn describe(cpu: &CPU, addr: usize) -> String {
+    Line::decode(cpu.mem.get_ref(), addr).mnemonic(&Labels::new())
+}
+
+fn stack_trace(cpu: &CPU, listing: &Listing) -> Value {
+    //the innermost frame is at the PC, callers at their CALLs, which the stack holds
+    let addrs = iter::once(cpu.pc).chain(cpu.stack.iter().rev().cloned());
+    let frames: Vec<Value> = addrs
+        .enumerate()
+        .map(|(id, addr)| {
+            let mut frame = json!({
+                "id": id,
+                "name": format!("{:04X} {}", addr, describe(cpu, addr)),
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": format!("0x{:04X}", addr),
+            });
+            //code outside the ROM's listing only shows in the disassembly view
+            if let Some(line) = listing.line_of(addr) {
+                frame["source"] = listing.source();
+                frame["line"] = json!(line);
+                frame["column"] = json!(1);
+            }
+            frame
+        })
+        .collect();
+    json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+fn variables(cpu: &CPU, reference: i64) -> Value {
+    let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+    let vars: Vec<Value> = match reference {
+        REGISTERS => cpu
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(n, v)| variable(format!("V{:X}", n), format!("0x{:02X}", v)))
+            .chain(vec![
+                variable("I".to_string(), format!("0x{:04X}", cpu.index_reg)),
+                variable("PC".to_string(), format!("0x{:04X}", cpu.pc)),
+            ])
+            .collect(),
+        TIMERS => vec![
+            variable("DT".to_string(), cpu.delay_timer.to_string()),
+            variable("ST".to_string(), cpu.sound_timer.to_string()),
+        ],
+        _ => vec![],
+    };
+    json!({ "variables": vars })
+}
+
+fn disassemble(cpu: &CPU, listing: &Listing, args: &Value) -> Value {
+    let base = args["memoryReference"]
+        .as_str()
+        .and_then(parse_address)
+        .unwrap_or(cpu.pc) as i64;
+    let offset =
+        args["offset"].as_i64().unwrap_or(0) + args["instructionOffset"].as_i64().unwrap_or(0) * 2;
+    let count = args["instructionCount"].as_i64().unwrap_or(0);
+
+    let instructions: Vec<Value> = (0..count)
+        .map(|n| base + offset + n * 2)
+        .map(|addr| {
+            if addr < 0 || opcode_at(cpu, addr as usize).is_none() {
+                return json!({ "address": format!("0x{:04X}", addr.max(0)), "instruction": "??" });
+            }
+            let addr = addr as usize;
+            let mut instruction = json!({
+                "address": format!("0x{:04X}", addr),
+                "instructionBytes": format!("{:04X}", opcode_at(cpu, addr).unwrap_or(0)),
+                "instruction": describe(cpu, addr),
+            });
+            if let Some(line) = listing.line_of(addr) {
+                instruction["location"] = listing.source();
+                instruction["line"] = json!(line);
+            }
+            instruction
+        })
+        .collect();
+    json!({ "instructions": instructions })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use serde_json::Value;
+
+    use chip8::frontend::{NullDisplay, NullInput};
+    use cli::{self, Options};
+
+    use super::{read_message, Listing, Server, LISTING};
+
+    //LD V0, 5; loop: ADD V0, 1; CALL sub; JP loop; sub: RET
+    const PROGRAM: &[u8] = &[0x60, 0x05, 0x70, 0x01, 0x22, 0x08, 0x12, 0x02, 0x00, 0xEE];
+
+    fn frame(message: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+    }
+
+    /// Every message the server wrote to `out`.
+    fn sent(mut out: &[u8]) -> Vec<Value> {
+        let mut messages = vec![];
+        while let Some(message) = read_message(&mut out).unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    /// "type command" or "type event" of each message, for checking the order.
+    fn kinds(messages: &[Value]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| {
+                let name = m["command"].as_str().or_else(|| m["event"].as_str());
+                format!(
+                    "{} {}",
+                    m["type"].as_str().unwrap_or(""),
+                    name.unwrap_or("")
+                )
+            })
+            .collect()
+    }
+
+    fn options() -> Options {
+        cli::parse(vec!["--dap".to_string()].into_iter()).unwrap()
+    }
+
+    #[test]
+    fn messages_are_content_length_framed() {
+        let input = format!(
+            "Content-Length: 9\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{{\"seq\":1}}{}",
+            frame("{\"seq\":2}")
+        );
+        let mut input = input.as_bytes();
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "seq": 1 })));
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "seq": 2 })));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let bad = frame("{\"seq\"");
+        assert!(read_message(&mut bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn listing_lines_skip_labels() {
+        let listing = Listing::new("roms/loop.ch8", PROGRAM);
+        assert_eq!(listing.name, "loop.ch8 (disassembly)");
+        let text: Vec<&str> = listing.text.lines().collect();
+        for &(line, addr) in &[(1, 0x200), (3, 0x202), (4, 0x204), (5, 0x206), (7, 0x208)] {
+            assert_eq!(listing.line_of(addr), Some(line));
+            assert!(text[line as usize - 1].starts_with(&format!("    {:03X}:", addr)));
+        }
+        assert_eq!(listing.line_of(0x203), None, "inside an instruction");
+        assert_eq!(listing.line_of(0x300), None, "past the ROM");
+    }
+
+    #[test]
+    fn breakpoints_on_labels_move_to_the_next_instruction() {
+        let listing = Listing::new("loop.ch8", PROGRAM);
+        assert_eq!(listing.instruction_at(1), Some((1, 0x200)));
+        assert_eq!(listing.instruction_at(2), Some((3, 0x202)));
+        assert_eq!(listing.instruction_at(6), Some((7, 0x208)));
+        assert_eq!(listing.instruction_at(0), Some((1, 0x200)));
+        assert_eq!(listing.instruction_at(8), None);
+    }
+
+    #[test]
+    fn instruction_breakpoints_before_address_zero_are_unverified() {
+        let options = options();
+        let mut server = Server::new(vec![], &options);
+        let request = json!({ "seq": 1, "command": "setInstructionBreakpoints",
+            "arguments": { "breakpoints": [
+                { "instructionReference": "0x200", "offset": 4 },
+                { "instructionReference": "0x200", "offset": -0x201 },
+                { "instructionReference": "nowhere" },
+            ] } });
+        assert!(server.handle(&request).unwrap());
+        assert_eq!(server.instruction_breakpoints, [0x204]);
+
+        let messages = sent(&server.out);
+        let breakpoints = &messages[0]["body"]["breakpoints"];
+        assert_eq!(
+            breakpoints[0],
+            json!({ "verified": true, "instructionReference": "0x0204" })
+        );
+        assert_eq!(breakpoints[1]["verified"], json!(false));
+        assert_eq!(breakpoints[2]["verified"], json!(false));
+    }
+
+    #[test]
+    fn launch_and_stop_at_a_breakpoint() {
+        let rom = env::temp_dir().join(format!("chip8-dap-{}.ch8", process::id()));
+        fs::write(&rom, PROGRAM).unwrap();
+        let options = options();
+        let mut server = Server::new(vec![], &options);
+
+        let requests = vec![
+            json!({ "seq": 1, "command": "initialize", "arguments": {} }),
+            json!({ "seq": 2, "command": "launch", "arguments": { "program": rom } }),
+            json!({ "seq": 3, "command": "setBreakpoints",
+                    "arguments": { "breakpoints": [{ "line": 6 }] } }),
+            json!({ "seq": 4, "command": "configurationDone" }),
+        ];
+        for request in &requests {
+            assert!(server.handle(request).unwrap());
+        }
+        for _ in 0..10 {
+            if !server.running {
+                break;
+            }
+            server.run_frame(&mut NullDisplay, &mut NullInput).unwrap();
+        }
+        assert!(server
+            .handle(&json!({ "seq": 5, "command": "stackTrace" }))
+            .unwrap());
+        fs::remove_file(&rom).unwrap();
+
+        let messages = sent(&server.out);
+        assert_eq!(
+            kinds(&messages),
+            [
+                "response initialize",
+                "response launch",
+                "event initialized",
+                "response setBreakpoints",
+                "response configurationDone",
+                "event stopped",
+                "response stackTrace",
+            ]
+        );
+        assert!(messages.iter().all(|m| m["success"] != json!(false)));
+        //line 6 is sub's label, so the breakpoint moves to its RET
+        assert_eq!(
+            messages[3]["body"]["breakpoints"][0],
+            json!({ "verified": true, "line": 7 })
+        );
+        assert_eq!(messages[5]["body"]["reason"], json!("breakpoint"));
+
+        let top = &messages[6]["body"]["stackFrames"][0];
+        assert_eq!(top["instructionPointerReference"], json!("0x0208"));
+        assert_eq!(top["line"], json!(7));
+        assert_eq!(top["source"]["sourceReference"], json!(LISTING));
+        let caller = &messages[6]["body"]["stackFrames"][1];
+        assert_eq!(caller["instructionPointerReference"], json!("0x0204"));
+        assert_eq!(caller["line"], json!(4));
+        assert_eq!(messages[6]["body"]["totalFrames"], json!(2));
+    }
+
+    #[test]
+    fn requests_before_launch_fail() {
+        let options = options();
+        let mut server = Server::new(vec![], &options);
+        server
+            .handle(&json!({ "seq": 1, "command": "stackTrace" }))
+            .unwrap();
+        let messages = sent(&server.out);
+        assert_eq!(messages[0]["success"], json!(false));
+        assert_eq!(messages[0]["message"], json!("no program is running"));
+    }
+}
@@ -0,0 +1,515 @@
+extern crate chip8;
+#[cfg(feature = "tui")]
+extern crate crossterm;
+#[cfg(feature = "dap")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const WAV_SAMPLE_RATE: u32 = 44_100;
+
+mod cli;
+#[cfg(feature = "dap")]
+mod dap;
+#[cfg(feature = "sdl")]
+mod sdl;
+#[cfg(feature = "tui")]
+mod terminal;
+
+use chip8::asm;
+use chip8::audio::WavRecorder;
+use chip8::coverage::Coverage;
+use chip8::cpu::*;
+use chip8::debugger::{self, Debugger, Outcome};
+use chip8::disasm;
+use chip8::frontend::{Runner, FRAME_RATE};
+use chip8::movie::{Movie, MovieError, Playback};
+use chip8::profile::{ProfileFormat, Profiler};
+use chip8::rewind::RewindBuffer;
+use chip8::rng::Rng;
+use chip8::savestate::SaveSlots;
+use chip8::trace::{self, TraceFilter, Tracer};
+use cli::{FrontendKind, Options};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("disasm") {
+        disasm(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("asm") {
+        assemble(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("tracediff") {
+        trace_diff(&args[1..]);
+        return;
+    }
+
+    let mut options = match cli::parse(args.into_iter()) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.dap {
+        run_dap(&options);
+        return;
+    }
+
+    let playback = options.play.as_ref().map(|path| {
+        match File::open(path)
+            .map_err(MovieError::from)
+            .and_then(Movie::read)
+        {
+            Ok(movie) => movie,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    });
+    if let Some(ref movie) = playback {
+        options.platform = movie.platform;
+        options.timing = movie.timing;
+        options.error_policy = movie.error_policy;
+    }
+
+    let rom = match fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("{}: {}", options.rom, e);
+            process::exit(1);
+        }
+    };
+    let mut cpu = match CPU::with_platform(&rom, options.platform) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: {}", options.rom, e);
+            process::exit(1);
+        }
+    };
+
+    cpu.error_policy = options.error_policy;
+    cpu.rng = match (playback.as_ref(), options.seed) {
+        (Some(movie), _) => movie.rng(),
+        (None, Some(seed)) => Rng::new(seed),
+        (None, None) => Rng::from_entropy(),
+    };
+    if let Some(ref path) = options.trace {
+        cpu.tracer = Some(tracer(path, &options.trace_filter));
+    }
+    if options.profile.is_some() || options.flamegraph.is_some() || options.callgraph.is_some() {
+        cpu.profiler = Some(Profiler::new(cpu.mem.get_ref().len()));
+    }
+    if options.coverage.is_some() || options.coverage_dump.is_some() {
+        cpu.coverage = Some(Coverage::new(cpu.mem.get_ref().len()));
+    }
+
+    if options.debug {
+        debug(&mut cpu, &options);
+        finish_trace(&mut cpu, &options);
+        write_profile(&mut cpu, &options);
+        write_coverage(&mut cpu, &options, rom.len());
+        return;
+    }
+
+    let mut runner = runner(&options);
+    if options.record.is_some() {
+        runner.recording = Some(Movie::new(&rom, &cpu, options.timing));
+    }
+    if let Some(movie) = playback {
+        if !movie.matches_rom(&rom) {
+            eprintln!("warning: the movie was recorded with a different ROM");
+        }
+        runner.playback = Some(Playback::new(movie));
+    }
+
+    let mut recorder = options
+        .wav
+        .as_ref()
+        .map(|_| WavRecorder::new(options.audio, WAV_SAMPLE_RATE));
+
+    let result = match options.frontend {
+        FrontendKind::Sdl => run_sdl(&mut runner, &mut cpu, &options, recorder.as_mut()),
+        FrontendKind::Terminal => run_terminal(&mut runner, &mut cpu, &options, recorder.as_mut()),
+    };
+
+    if let (Some(path), Some(recorder)) = (options.wav.as_ref(), recorder) {
+        if let Err(e) = File::create(path).and_then(|f| recorder.write_wav(BufWriter::new(f))) {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+
+    if let (Some(path), Some(movie)) = (options.record.as_ref(), runner.recording) {
+        if let Err(e) = File::create(path).and_then(|f| movie.write(BufWriter::new(f))) {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+    let desynced = runner.playback.is_some_and(|p| p.desync.is_some());
+    finish_trace(&mut cpu, &options);
+    write_profile(&mut cpu, &options);
+    write_coverage(&mut cpu, &options, rom.len());
+
+    if let Err(e) = result {
+        //the seed reproduces the run with --seed
+        eprintln!("{}: {} (seed {})", options.rom, e, cpu.rng.seed);
+        process::exit(1);
+    }
+    if desynced {
+        process::exit(1);
+    }
+}
+
+/// Opens the `--trace` output; `-` is stderr.
+fn tracer(path: &str, filter: &TraceFilter) -> Tracer {
+    if path == "-" {
+        return Tracer::new(BufWriter::new(io::stderr()), filter.clone());
+    }
+    match File::create(path) {
+        Ok(f) => Tracer::new(BufWriter::new(f), filter.clone()),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn finish_trace(cpu: &mut CPU, options: &Options) {
+    if let (Some(path), Some(tracer)) = (options.trace.as_ref(), cpu.tracer.take()) {
+        if let Err(e) = tracer.finish() {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+}
+
+/// Writes the `--profile` report, `-` being stdout, and the call graphs.
+fn write_profile(cpu: &mut CPU, options: &Options) {
+    let profiler = match cpu.profiler.take() {
+        Some(profiler) => profiler,
+        None => return,
+    };
+    let report_error = |path: &str, e: io::Error| eprintln!("{}: {}", path, e);
+
+    if let Some(ref path) = options.profile {
+        let report = profiler.report(cpu.mem.get_ref(), options.profile_top as usize);
+        let text = match options.profile_format {
+            ProfileFormat::Text => report.to_string(),
+            ProfileFormat::Json => report.to_json() + "\n",
+        };
+        let written = if path == "-" {
+            io::stdout().write_all(text.as_bytes())
+        } else {
+            fs::write(path, text)
+        };
+        if let Err(e) = written {
+            report_error(path, e);
+        }
+    }
+    if let Some(ref path) = options.flamegraph {
+        if let Err(e) = File::create(path).and_then(|f| profiler.write_folded(BufWriter::new(f))) {
+            report_error(path, e);
+        }
+    }
+    if let Some(ref path) = options.callgraph {
+        if let Err(e) = File::create(path).and_then(|f| profiler.write_dot(BufWriter::new(f))) {
+            report_error(path, e);
+        }
+    }
+}
+
+/// Writes the `--coverage` report and `--coverage-dump` hex dump for a ROM
+/// of `rom_len` bytes.
+fn write_coverage(cpu: &mut CPU, options: &Options, rom_len: usize) {
+    let coverage = match cpu.coverage.take() {
+        Some(coverage) => coverage,
+        None => return,
+    };
+    if let Some(ref path) = options.coverage {
+        if let Err(e) = fs::write(path, coverage.report(rom_len).to_string()) {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+    if let Some(ref path) = options.coverage_dump {
+        let written = File::create(path)
+            .and_then(|f| coverage.write_hexdump(cpu.mem.get_ref(), rom_len, BufWriter::new(f)));
+        if let Err(e) = written {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+}
+
+/// Prints a listing of the ROM named in `args`.
+fn disasm(args: &[String]) {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("disasm takes one ROM\n{}", cli::USAGE);
+            process::exit(2);
+        }
+    };
+    match fs::read(path) {
+        //a closed pipe, e.g. into `head`, is not an error worth reporting
+        Ok(rom) => {
+            let _ = write!(io::stdout(), "{}", disasm::disassemble(&rom));
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Assembles the source named in `args` into a ROM and a symbol file.
+fn assemble(args: &[String]) {
+    let (source, rom) = match args {
+        [source] => (source, Path::new(source).with_extension("ch8")),
+        [source, flag, rom] if flag == "-o" => (source, PathBuf::from(rom)),
+        _ => {
+            eprintln!(
+                "asm takes one source file and an optional -o ROM\n{}",
+                cli::USAGE
+            );
+            process::exit(2);
+        }
+    };
+    let assembly = match asm::assemble_file(source) {
+        Ok(assembly) => assembly,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let mut sym = rom.clone().into_os_string();
+    sym.push(".sym");
+    let written = fs::write(&rom, &assembly.rom)
+        .map_err(|e| (rom.clone(), e))
+        .and_then(|_| {
+            File::create(&sym)
+                .and_then(|f| assembly.write_symbols(BufWriter::new(f)))
+                .map_err(|e| (PathBuf::from(&sym), e))
+        });
+    if let Err((path, e)) = written {
+        eprintln!("{}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+/// Compares the two traces named in `args`, exiting with 1 if they diverge.
+fn trace_diff(args: &[String]) {
+    let (paths, context) = match args {
+        [a, b] => ([a, b], 5),
+        [a, b, flag, n] if flag == "--context" => match n.parse() {
+            Ok(n) => ([a, b], n),
+            Err(_) => {
+                eprintln!("--context expects a number, got {}", n);
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!(
+                "tracediff takes two traces and an optional --context N\n{}",
+                cli::USAGE
+            );
+            process::exit(2);
+        }
+    };
+    //errors are reported with the file they came from as soon as they are read
+    let open = |path: &String| {
+        let records = match File::open(path) {
+            Ok(f) => trace::records(io::BufReader::new(f)),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(1);
+            }
+        };
+        let path = path.clone();
+        records.map(move |record| {
+            Ok(record.unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                process::exit(1);
+            }))
+        })
+    };
+    match trace::diff(open(paths[0]), open(paths[1]), context) {
+        Ok(None) => println!("the traces match"),
+        Ok(Some(divergence)) => {
+            print!("{}", divergence);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Reads debugger commands from stdin until `quit` or end of input.
+fn debug(cpu: &mut CPU, options: &Options) {
+    let mut debugger = Debugger::new(options.timing);
+    println!("{}", debugger::HELP);
+    let stdin = io::stdin();
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match debugger.execute(cpu, &line) {
+            Ok(Outcome::Output(text)) if text.is_empty() => {}
+            Ok(Outcome::Output(text)) => println!("{}", text),
+            Ok(Outcome::Quit) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// A runner for `options`, with save slots beside the ROM.
+fn runner(options: &Options) -> Runner {
+    let mut runner = Runner::new(options.timing);
+    runner.save_slots = Some(SaveSlots::for_rom(&options.rom));
+    runner.on_event = Some(Box::new(|event| eprintln!("{}", event)));
+    if options.rewind_seconds > 0 {
+        let frames = options.rewind_seconds * FRAME_RATE;
+        runner.rewind = Some(RewindBuffer::new(frames as usize));
+    }
+    runner
+}
+
+#[cfg(feature = "dap")]
+fn run_dap(options: &Options) {
+    use chip8::frontend::{NullDisplay, NullInput};
+
+    let mut server = dap::Server::new(io::stdout(), options);
+
+    //show the game in a window when there is a display to put it on
+    #[cfg(feature = "sdl")]
+    {
+        if let Ok(mut display) = sdl::SdlDisplay::new() {
+            if let Ok(mut input) = sdl::SdlInput::new(&display.ctx) {
+                if let Err(e) = server.serve(&mut display, &mut input) {
+                    eprintln!("dap: {}", e);
+                }
+                return;
+            }
+        }
+    }
+
+    if let Err(e) = server.serve(&mut NullDisplay, &mut NullInput) {
+        eprintln!("dap: {}", e);
+    }
+}
+
+#[cfg(not(feature = "dap"))]
+fn run_dap(_options: &Options) {
+    eprintln!("chip8 was built without the `dap` feature");
+    process::exit(1);
+}
+
+#[cfg(feature = "sdl")]
+fn run_sdl(
+    runner: &mut Runner,
+    cpu: &mut CPU,
+    options: &Options,
+    recorder: Option<&mut WavRecorder>,
+) -> Result<(), CpuError> {
+    use chip8::frontend::{Audio, NullAudio};
+
+    let mut display = match sdl::SdlDisplay::new() {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("cannot open a window: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut input = match sdl::SdlInput::new(&display.ctx) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!("cannot read the keyboard: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut device = None;
+    let audio: &mut dyn Audio = match recorder {
+        Some(recorder) => recorder,
+        None => match sdl::SdlAudio::new(&display.ctx, options.audio) {
+            Ok(a) => device.get_or_insert(a),
+            Err(e) => {
+                eprintln!("no audio: {}", e);
+                &mut NullAudio
+            }
+        },
+    };
+
+    runner.run(cpu, &mut display, &mut input, audio)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_sdl(
+    _runner: &mut Runner,
+    _cpu: &mut CPU,
+    _options: &Options,
+    _recorder: Option<&mut WavRecorder>,
+) -> Result<(), CpuError> {
+    eprintln!("chip8 was built without the `sdl` feature");
+    process::exit(1);
+}
+
+#[cfg(feature = "tui")]
+fn run_terminal(
+    runner: &mut Runner,
+    cpu: &mut CPU,
+    options: &Options,
+    recorder: Option<&mut WavRecorder>,
+) -> Result<(), CpuError> {
+    use chip8::frontend::Audio;
+
+    let _terminal = match terminal::Terminal::new() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("cannot set up the terminal: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut bell = terminal::TerminalBell::default();
+    if options.audio.muted {
+        bell.toggle_mute();
+    }
+    let audio: &mut dyn Audio = match recorder {
+        Some(recorder) => recorder,
+        None => &mut bell,
+    };
+
+    runner.run(
+        cpu,
+        &mut terminal::TerminalDisplay::default(),
+        &mut terminal::TerminalInput::default(),
+        audio,
+    )
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_terminal(
+    _runner: &mut Runner,
+    _cpu: &mut CPU,
+    _options: &Options,
+    _recorder: Option<&mut WavRecorder>,
+) -> Result<(), CpuError> {
+    eprintln!("chip8 was built without the `tui` feature");
+    process::exit(1);
+}
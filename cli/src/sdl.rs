@@ -0,0 +1,262 @@
+use std::fmt;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::video::WindowBuildError;
+use sdl2::IntegerOrSdlError;
+
+use chip8::audio::{AudioConfig, Beeper, ToneGenerator};
+use chip8::frontend::{qwerty_keypad, Audio, Command, Display, Input};
+use chip8::gpu::{GPU, HEIGHT, WIDTH};
+
+const SCALE: u32 = 10; //window pixels per low resolution pixel
+
+#[derive(Debug)]
+pub enum SdlError {
+    Window { inner: WindowBuildError },
+    Sdl { inner: IntegerOrSdlError },
+    Common(String),
+}
+
+impl fmt::Display for SdlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SdlError::Window { ref inner } => write!(f, "{}", inner),
+            SdlError::Sdl { ref inner } => write!(f, "{}", inner),
+            SdlError::Common(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<String> for SdlError {
+    fn from(error_msg: String) -> Self {
+        SdlError::Common(error_msg)
+    }
+}
+
+impl From<WindowBuildError> for SdlError {
+    fn from(window_error: WindowBuildError) -> Self {
+        SdlError::Window {
+            inner: window_error,
+        }
+    }
+}
+
+impl From<IntegerOrSdlError> for SdlError {
+    fn from(sdl_error: IntegerOrSdlError) -> Self {
+        SdlError::Sdl { inner: sdl_error }
+    }
+}
+
+struct GConfig {
+    background_color: Color,
+
+    plane_colors: [Color; 3], //plane 1, plane 2, both (XO-CHIP)
+}
+
+pub struct SdlDisplay {
+    pub ctx: sdl2::Sdl, //sdl context
+
+    pub canvas: sdl2::render::Canvas<sdl2::video::Window>, //sdl canvas
+
+    config: GConfig, //color configs
+}
+
+impl SdlDisplay {
+    pub fn new() -> Result<SdlDisplay, SdlError> {
+        let sdl_context = sdl2::init()?;
+        let video_subsystem = sdl_context.video()?;
+        let window = video_subsystem
+            .window("CHIP-8", WIDTH as u32 * SCALE, HEIGHT as u32 * SCALE)
+            .position_centered()
+            .opengl()
+            .build()?;
+
+        let canvas = window.into_canvas().build()?;
+
+        Ok(SdlDisplay {
+            ctx: sdl_context,
+            canvas,
+            config: GConfig {
+                background_color: Color::RGB(0, 0, 0),
+                plane_colors: [
+                    Color::RGB(255, 255, 255),
+                    Color::RGB(255, 170, 0),
+                    Color::RGB(170, 85, 0),
+                ],
+            },
+        })
+    }
+
+    fn reset(&mut self) {
+        self.canvas.set_draw_color(self.config.background_color);
+        self.canvas.clear();
+    }
+}
+
+impl Display for SdlDisplay {
+    fn present(&mut self, gpu: &GPU) {
+        self.reset();
+        let scale = SCALE * WIDTH as u32 / gpu.width() as u32;
+        for y in 0..gpu.height() {
+            for x in 0..gpu.width() {
+                let pixel = gpu.pixel(x, y) as usize;
+                if pixel != 0 {
+                    self.canvas
+                        .set_draw_color(self.config.plane_colors[pixel - 1]);
+                    let _result = self.canvas.fill_rect(Rect::new(
+                        x as i32 * scale as i32,
+                        y as i32 * scale as i32,
+                        scale,
+                        scale,
+                    ));
+                }
+            }
+        }
+        self.canvas.present();
+    }
+}
+
+pub struct SdlInput {
+    event_pump: sdl2::EventPump,
+}
+
+impl SdlInput {
+    pub fn new(ctx: &sdl2::Sdl) -> Result<SdlInput, SdlError> {
+        Ok(SdlInput {
+            event_pump: ctx.event_pump()?,
+        })
+    }
+}
+
+/// The QWERTY layout plus the arrow keys and Enter, which are 4, 6, 8, 2 and 5
+/// on the keypad.
+fn keypad_index(keycode: Keycode) -> Option<usize> {
+    match keycode {
+        Keycode::Left => Some(4),
+        Keycode::Right => Some(6),
+        Keycode::Up => Some(8),
+        Keycode::Down => Some(2),
+        Keycode::Return => Some(5),
+        _ => {
+            let name = keycode.name();
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => qwerty_keypad(c),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// F1-F4 save to slots 1-4, F5-F8 load them.
+fn state_command(keycode: Keycode) -> Option<Command> {
+    match keycode {
+        Keycode::F1 => Some(Command::SaveState(1)),
+        Keycode::F2 => Some(Command::SaveState(2)),
+        Keycode::F3 => Some(Command::SaveState(3)),
+        Keycode::F4 => Some(Command::SaveState(4)),
+        Keycode::F5 => Some(Command::LoadState(1)),
+        Keycode::F6 => Some(Command::LoadState(2)),
+        Keycode::F7 => Some(Command::LoadState(3)),
+        Keycode::F8 => Some(Command::LoadState(4)),
+        _ => None,
+    }
+}
+
+impl Input for SdlInput {
+    fn poll(&mut self, keys: &mut [u8; 16]) -> Vec<Command> {
+        let mut commands = vec![];
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => commands.push(Command::Quit),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Space),
+                    ..
+                } => keys[15] ^= 1,
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    ..
+                } => commands.push(Command::ToggleMute),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    repeat: false,
+                    ..
+                } => commands.push(Command::Rewind(true)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => commands.push(Command::Rewind(false)),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(i) = keypad_index(keycode) {
+                        keys[i] = 1;
+                    }
+                    if let Some(command) = state_command(keycode) {
+                        commands.push(command);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(i) = keypad_index(keycode) {
+                        keys[i] = 0;
+                    }
+                }
+                _ => {}
+            }
+        }
+        commands
+    }
+}
+
+struct Tone(ToneGenerator);
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.fill(out);
+    }
+}
+
+/// Plays the beeper through the default SDL audio device.
+pub struct SdlAudio {
+    device: AudioDevice<Tone>,
+}
+
+impl SdlAudio {
+    pub fn new(ctx: &sdl2::Sdl, config: AudioConfig) -> Result<SdlAudio, SdlError> {
+        let spec = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = ctx.audio()?.open_playback(None, &spec, |spec| {
+            Tone(ToneGenerator::new(config, spec.freq as u32))
+        })?;
+        device.resume();
+        Ok(SdlAudio { device })
+    }
+}
+
+impl Audio for SdlAudio {
+    fn update(&mut self, beeper: &Beeper) {
+        self.device.lock().0.beeper = *beeper;
+    }
+
+    fn toggle_mute(&mut self) {
+        let mut tone = self.device.lock();
+        tone.0.config.muted = !tone.0.config.muted;
+    }
+}
@@ -0,0 +1,175 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use chip8::audio::Beeper;
+use chip8::frontend::{qwerty_keypad, Audio, Command, Display, Input};
+use chip8::gpu::GPU;
+
+// Most terminals only report key presses, so a key counts as held until no
+// press (or auto-repeat) has been seen for this long.
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+/// Puts the terminal into raw mode on an alternate screen and restores it
+/// when dropped.
+pub struct Terminal;
+
+impl Terminal {
+    pub fn new() -> io::Result<Terminal> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            io::stdout(),
+            EnterAlternateScreen,
+            Hide,
+            Clear(ClearType::All)
+        )?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _result = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _result = terminal::disable_raw_mode();
+    }
+}
+
+/// Draws the framebuffer with half-block characters, two pixel rows per line.
+#[derive(Default)]
+pub struct TerminalDisplay {
+    last_frame: Vec<u8>,
+}
+
+fn half_block(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (true, true) => '█',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (false, false) => ' ',
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn present(&mut self, gpu: &GPU) {
+        // redrawing an unchanged frame only wastes bandwidth over ssh
+        if self.last_frame[..] == gpu.gfx[..] {
+            return;
+        }
+        if self.last_frame.len() != gpu.gfx.len() {
+            //the resolution changed, so the old frame may stick out
+            let _result = execute!(io::stdout(), Clear(ClearType::All));
+        }
+        self.last_frame = gpu.gfx.to_vec();
+
+        let mut out = io::stdout();
+        for row in 0..gpu.height() / 2 {
+            let line: String = (0..gpu.width())
+                .map(|x| half_block(gpu.pixel(x, row * 2) != 0, gpu.pixel(x, row * 2 + 1) != 0))
+                .collect();
+            let _result = queue!(out, MoveTo(0, row as u16), Print(line));
+        }
+        let _result = out.flush();
+    }
+}
+
+/// Reads raw-mode keystrokes into the keypad using the QWERTY layout (plus the
+/// arrow keys and Enter, as in the SDL frontend). Esc or Ctrl-C quits, M
+/// toggles the bell.
+#[derive(Default)]
+pub struct TerminalInput {
+    pressed_at: [Option<Instant>; 16],
+}
+
+fn keypad_index(code: KeyCode) -> Option<usize> {
+    match code {
+        KeyCode::Char(c) => qwerty_keypad(c),
+        KeyCode::Left => Some(4),
+        KeyCode::Right => Some(6),
+        KeyCode::Up => Some(8),
+        KeyCode::Down => Some(2),
+        KeyCode::Enter => Some(5),
+        _ => None,
+    }
+}
+
+impl Input for TerminalInput {
+    fn poll(&mut self, keys: &mut [u8; 16]) -> Vec<Command> {
+        let mut commands = vec![];
+
+        while let Ok(true) = event::poll(Duration::from_secs(0)) {
+            let key = match event::read() {
+                Ok(Event::Key(key)) => key,
+                Ok(_) => continue,
+                Err(_) => break,
+            };
+            match key {
+                KeyEvent {
+                    code: KeyCode::Esc, ..
+                } => commands.push(Command::Quit),
+                KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers,
+                    ..
+                } if modifiers.contains(KeyModifiers::CONTROL) => commands.push(Command::Quit),
+                KeyEvent {
+                    code: KeyCode::Char('m'),
+                    kind: KeyEventKind::Press,
+                    ..
+                } => commands.push(Command::ToggleMute),
+                KeyEvent {
+                    code: KeyCode::F(n @ 1..=8),
+                    kind: KeyEventKind::Press,
+                    ..
+                } => commands.push(if n <= 4 {
+                    Command::SaveState(n)
+                } else {
+                    Command::LoadState(n - 4)
+                }),
+                KeyEvent { code, kind, .. } => {
+                    if let Some(i) = keypad_index(code) {
+                        self.pressed_at[i] = match kind {
+                            KeyEventKind::Release => None,
+                            _ => Some(Instant::now()),
+                        };
+                    }
+                }
+            }
+        }
+
+        for (key, pressed_at) in keys.iter_mut().zip(self.pressed_at.iter_mut()) {
+            if pressed_at.is_some_and(|t| t.elapsed() > KEY_HOLD) {
+                *pressed_at = None;
+            }
+            *key = pressed_at.is_some() as u8;
+        }
+
+        commands
+    }
+}
+
+/// Rings the terminal bell whenever the beeper starts, the closest a terminal
+/// gets to a tone.
+#[derive(Default)]
+pub struct TerminalBell {
+    was_on: bool,
+    muted: bool,
+}
+
+impl Audio for TerminalBell {
+    fn update(&mut self, beeper: &Beeper) {
+        if beeper.on && !self.was_on && !self.muted {
+            let mut out = io::stdout();
+            let _result = out.write_all(b"\x07").and_then(|_| out.flush());
+        }
+        self.was_on = beeper.on;
+    }
+
+    fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+}
@@ -0,0 +1,807 @@
+//! An assembler for the mnemonics `Instructions` displays as.
+//!
+//! ```text
+//! ; comments run to the end of the line
+//! SPEED = 2               ; constants, also `SPEED EQU 2`
+//! start:                  ; labels
+//!     LD I, sprite
+//!     DRW V0, V1, 5
+//!     ADD V0, SPEED * 4   ; expressions: + - * / % & | ^ << >> ~ and ( )
+//!     JP $                ; `$` is the address of the line it is on
+//! sprite:
+//!     DB 0xF0, 0x90, 0b1111_0000, "text"
+//!     DW 0x1234
+//!     ORG 0x300           ; continue assembling at an address
+//!     INCLUDE "more.asm"  ; relative to the including file
+//! ```
+//!
+//! Mnemonics and register names are case-insensitive, symbols are not.
+//! Register and operand names such as `V0`, `I`, `DT` or `K` cannot be used as
+//! symbols. F000 NNNN is written `LD I, LONG addr`.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use cpu::PROGRAM_START;
+
+/// Where assembly failed and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+
+    pub line: usize, //1-based
+
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl error::Error for AsmError {}
+
+/// An assembled program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub rom: Vec<u8>, //loaded at PROGRAM_START
+
+    pub labels: BTreeMap<String, usize>,
+}
+
+impl Assembly {
+    /// Writes the labels as `ADDR NAME` lines in address order. Constants are
+    /// left out: the file maps addresses to names, and a constant such as
+    /// `SPEED = 2` would name address 0002.
+    pub fn write_symbols<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut by_address: Vec<(&usize, &String)> = self
+            .labels
+            .iter()
+            .map(|(name, addr)| (addr, name))
+            .collect();
+        by_address.sort();
+        for (addr, name) in by_address {
+            writeln!(out, "{:04X} {}", addr, name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Assembles `source`. Includes are resolved against the current directory.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut statements = vec![];
+    parse_source(source, "<source>", Path::new("."), 0, &mut statements)?;
+    Assembler::default().run(&statements)
+}
+
+/// Assembles the file at `path`.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Assembly, AsmError> {
+    let mut statements = vec![];
+    include(path.as_ref(), "<command line>", 0, 0, &mut statements)?;
+    Assembler::default().run(&statements)
+}
+
+/// Includes nested deeper than this are assumed to be recursive.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+fn include(
+    path: &Path,
+    from: &str,
+    line: usize,
+    depth: usize,
+    statements: &mut Vec<Statement>,
+) -> Result<(), AsmError> {
+    let error = |message: String| AsmError {
+        file: from.to_string(),
+        line,
+        message,
+    };
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(error("includes nest too deeply".to_string()));
+    }
+    let source =
+        fs::read_to_string(path).map_err(|e| error(format!("{}: {}", path.display(), e)))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    parse_source(&source, &path.display().to_string(), dir, depth, statements)
+}
+
+#[derive(Debug, Clone)]
+struct Statement {
+    file: Rc<String>,
+
+    line: usize,
+
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Label(String),
+
+    Constant(String, Expr),
+
+    Org(Expr),
+
+    Data {
+        width: usize,
+        items: Vec<Datum>,
+    },
+
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Datum {
+    Bytes(Vec<u8>), //a string
+
+    Value(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    V(u8),
+
+    I,
+
+    IndirectI, //[I]
+
+    DT,
+
+    ST,
+
+    K,
+
+    F,
+
+    HF,
+
+    B,
+
+    R,
+
+    Long(Expr), //LONG addr, the F000 NNNN operand
+
+    Value(Expr),
+}
+
+fn parse_source(
+    source: &str,
+    file: &str,
+    dir: &Path,
+    depth: usize,
+    statements: &mut Vec<Statement>,
+) -> Result<(), AsmError> {
+    let file = Rc::new(file.to_string());
+    for (n, text) in source.lines().enumerate() {
+        let line = n + 1;
+        let error = |message: String| AsmError {
+            file: file.to_string(),
+            line,
+            message,
+        };
+        let push = |statements: &mut Vec<Statement>, kind| {
+            statements.push(Statement {
+                file: file.clone(),
+                line,
+                kind,
+            })
+        };
+
+        let mut text = strip_comment(text).trim();
+
+        //label:
+        if let Some(colon) = text.find(':') {
+            let name = text[..colon].trim();
+            if is_symbol(name) {
+                definable(name).map_err(error)?;
+                push(statements, Kind::Label(name.to_string()));
+                text = text[colon + 1..].trim();
+            }
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (word, rest) = split_word(text);
+
+        //NAME = expr, NAME EQU expr
+        let (next, value) = split_word(rest);
+        if is_symbol(word) && (next == "=" || next.eq_ignore_ascii_case("equ")) {
+            definable(word).map_err(error)?;
+            let expr = parse_expr(value).map_err(error)?;
+            push(statements, Kind::Constant(word.to_string(), expr));
+            continue;
+        }
+        if let Some(value) = rest.strip_prefix('=').filter(|_| is_symbol(word)) {
+            definable(word).map_err(error)?;
+            let expr = parse_expr(value).map_err(error)?;
+            push(statements, Kind::Constant(word.to_string(), expr));
+            continue;
+        }
+
+        let mnemonic = word.to_ascii_uppercase();
+        let kind = match mnemonic.as_str() {
+            "INCLUDE" => {
+                let name = parse_string(rest.trim()).map_err(error)?;
+                let path: PathBuf = dir.join(String::from_utf8_lossy(&name).as_ref());
+                include(&path, &file, line, depth + 1, statements)?;
+                continue;
+            }
+            "ORG" => Kind::Org(parse_expr(rest).map_err(error)?),
+            "DB" | "DW" => {
+                let items = split_operands(rest)
+                    .iter()
+                    .map(|item| {
+                        if item.starts_with('"') {
+                            parse_string(item).map(Datum::Bytes)
+                        } else {
+                            parse_expr(item).map(Datum::Value)
+                        }
+                    })
+                    .collect::<Result<Vec<Datum>, String>>()
+                    .map_err(error)?;
+                let width = if mnemonic == "DB" { 1 } else { 2 };
+                Kind::Data { width, items }
+            }
+            _ => {
+                let operands = split_operands(rest)
+                    .iter()
+                    .map(|op| parse_operand(op))
+                    .collect::<Result<Vec<Operand>, String>>()
+                    .map_err(error)?;
+                Kind::Instruction { mnemonic, operands }
+            }
+        };
+        push(statements, kind);
+    }
+    Ok(())
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, ""),
+    }
+}
+
+/// Splits on commas outside quotes and parentheses.
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = vec![];
+    let mut current = String::new();
+    let (mut depth, mut quoted) = (0, false);
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                operands.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    operands
+}
+
+fn is_symbol(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Operand names that a label or constant would be read as instead.
+const RESERVED: &[&str] = &["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"];
+
+/// Checks a symbol can name a label or constant.
+fn definable(name: &str) -> Result<(), String> {
+    if register(name).is_some() || RESERVED.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+        return Err(format!("{} is a register or operand name", name));
+    }
+    Ok(())
+}
+
+fn register(s: &str) -> Option<u8> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('V'), Some(n), None) | (Some('v'), Some(n), None) => n.to_digit(16).map(|n| n as u8),
+        _ => None,
+    }
+}
+
+fn parse_string(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return Err(format!("expected a quoted string, got {}", s));
+    }
+    Ok(s.as_bytes()[1..s.len() - 1].to_vec())
+}
+
+fn parse_operand(op: &str) -> Result<Operand, String> {
+    if let Some(n) = register(op) {
+        return Ok(Operand::V(n));
+    }
+    let (word, rest) = split_word(op);
+    if word.eq_ignore_ascii_case("long") && !rest.is_empty() {
+        return parse_expr(rest).map(Operand::Long);
+    }
+    Ok(match op.to_ascii_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::HF,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ => Operand::Value(parse_expr(op)?),
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+
+    Symbol(String),
+
+    Here, //$
+
+    Negate(Box<Expr>),
+
+    Not(Box<Expr>),
+
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    Xor,
+    And,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// Binary operators by precedence, loosest first.
+const PRECEDENCE: &[&[(&str, BinOp)]] = &[
+    &[("|", BinOp::Or)],
+    &[("^", BinOp::Xor)],
+    &[("&", BinOp::And)],
+    &[("<<", BinOp::Shl), (">>", BinOp::Shr)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    Op(String),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+            tokens.push(Token::Number(parse_number(&text)?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Symbol(chars[start..i].iter().collect()));
+        } else if c == '\'' && i + 2 < chars.len() && chars[i + 2] == '\'' {
+            tokens.push(Token::Number(chars[i + 1] as i64));
+            i += 3;
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if two == "<<" || two == ">>" {
+                tokens.push(Token::Op(two));
+                i += 2;
+            } else if "+-*/%&|^~()$".contains(c) {
+                tokens.push(Token::Op(c.to_string()));
+                i += 1;
+            } else {
+                return Err(format!("unexpected {} in expression", c));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let lower = text.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else {
+        lower.parse()
+    };
+    parsed.map_err(|_| format!("bad number {}", text))
+}
+
+fn parse_expr(s: &str) -> Result<Expr, String> {
+    let tokens = tokenize(s)?;
+    if tokens.is_empty() {
+        return Err("missing expression".to_string());
+    }
+    let mut pos = 0;
+    let expr = parse_binary(&tokens, &mut pos, 0)?;
+    match tokens.get(pos) {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {:?} in expression", token)),
+    }
+}
+
+fn parse_binary(tokens: &[Token], pos: &mut usize, level: usize) -> Result<Expr, String> {
+    if level == PRECEDENCE.len() {
+        return parse_unary(tokens, pos);
+    }
+    let mut lhs = parse_binary(tokens, pos, level + 1)?;
+    loop {
+        let op = match tokens.get(*pos) {
+            Some(Token::Op(op)) => PRECEDENCE[level].iter().find(|(s, _)| s == op),
+            _ => None,
+        };
+        match op {
+            Some(&(_, op)) => {
+                *pos += 1;
+                let rhs = parse_binary(tokens, pos, level + 1)?;
+                lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+            }
+            None => return Ok(lhs),
+        }
+    }
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*pos).cloned();
+    *pos += 1;
+    match token {
+        Some(Token::Number(n)) => Ok(Expr::Number(n)),
+        Some(Token::Symbol(name)) => Ok(Expr::Symbol(name)),
+        Some(Token::Op(ref op)) if op == "$" => Ok(Expr::Here),
+        Some(Token::Op(ref op)) if op == "-" => {
+            Ok(Expr::Negate(Box::new(parse_unary(tokens, pos)?)))
+        }
+        Some(Token::Op(ref op)) if op == "~" => Ok(Expr::Not(Box::new(parse_unary(tokens, pos)?))),
+        Some(Token::Op(ref op)) if op == "(" => {
+            let expr = parse_binary(tokens, pos, 0)?;
+            match tokens.get(*pos) {
+                Some(Token::Op(ref op)) if op == ")" => {
+                    *pos += 1;
+                    Ok(expr)
+                }
+                _ => Err("missing )".to_string()),
+            }
+        }
+        Some(token) => Err(format!("unexpected {:?} in expression", token)),
+        None => Err("expression ends early".to_string()),
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    labels: HashMap<String, usize>,
+
+    constants: HashMap<String, (Expr, usize)>, //with the address they were defined at, for `$`
+
+    values: RefCell<HashMap<String, i64>>, //constants evaluated so far, each only once
+
+    resolving: RefCell<Vec<String>>, //constants being evaluated, to catch cycles
+}
+
+impl Assembler {
+    fn eval(&self, expr: &Expr, here: usize) -> Result<i64, String> {
+        let eval = |e: &Expr| self.eval(e, here);
+        Ok(match *expr {
+            Expr::Number(n) => n,
+            Expr::Here => here as i64,
+            Expr::Symbol(ref name) => {
+                if let Some(addr) = self.labels.get(name) {
+                    *addr as i64
+                } else if let Some(&(ref value, defined)) = self.constants.get(name) {
+                    self.constant(name, value, defined)?
+                } else {
+                    return Err(format!("undefined symbol {}", name));
+                }
+            }
+            Expr::Negate(ref e) => eval(e)?.wrapping_neg(),
+            Expr::Not(ref e) => !eval(e)?,
+            Expr::Binary(op, ref a, ref b) => {
+                let (a, b) = (eval(a)?, eval(b)?);
+                match op {
+                    BinOp::Or => a | b,
+                    BinOp::Xor => a ^ b,
+                    BinOp::And => a & b,
+                    BinOp::Shl => a.wrapping_shl(b as u32),
+                    BinOp::Shr => a.wrapping_shr(b as u32),
+                    BinOp::Add => a.wrapping_add(b),
+                    BinOp::Sub => a.wrapping_sub(b),
+                    BinOp::Mul => a.wrapping_mul(b),
+                    BinOp::Div | BinOp::Rem if b == 0 => return Err("division by zero".to_string()),
+                    BinOp::Div => a.checked_div(b).ok_or("division overflows")?,
+                    BinOp::Rem => a.checked_rem(b).ok_or("division overflows")?,
+                }
+            }
+        })
+    }
+
+    /// The value of the constant `name`, defined as `value` at `defined`.
+    /// A constant that only fails for now, because it uses a label further
+    /// on, is evaluated again next time it is needed.
+    fn constant(&self, name: &str, value: &Expr, defined: usize) -> Result<i64, String> {
+        let cached = self.values.borrow().get(name).cloned();
+        if let Some(v) = cached {
+            return Ok(v);
+        }
+        if self.resolving.borrow().iter().any(|n| n == name) {
+            return Err(format!("{} is defined in terms of itself", name));
+        }
+
+        self.resolving.borrow_mut().push(name.to_string());
+        let v = self.eval(value, defined);
+        self.resolving.borrow_mut().pop();
+        let v = v?;
+        self.values.borrow_mut().insert(name.to_string(), v);
+        Ok(v)
+    }
+
+    /// Lays out the program, then encodes it.
+    fn run(mut self, statements: &[Statement]) -> Result<Assembly, AsmError> {
+        let mut addresses = Vec::with_capacity(statements.len());
+        let mut here = PROGRAM_START;
+        for statement in statements {
+            let error = |message: String| statement.error(message);
+            addresses.push(here);
+            match statement.kind {
+                Kind::Label(ref name) => {
+                    if self.defined(name) {
+                        return Err(error(format!("{} is already defined", name)));
+                    }
+                    self.labels.insert(name.clone(), here);
+                }
+                Kind::Constant(ref name, ref expr) => {
+                    if self.defined(name) {
+                        return Err(error(format!("{} is already defined", name)));
+                    }
+                    self.constants.insert(name.clone(), (expr.clone(), here));
+                }
+                Kind::Org(ref expr) => {
+                    let org = self
+                        .eval(expr, here)
+                        .map_err(|e| error(format!("ORG needs a value known by then: {}", e)))?;
+                    if org < here as i64 {
+                        return Err(error(format!("ORG 0x{:X} goes backwards", org)));
+                    }
+                    here = org as usize;
+                }
+                Kind::Data { width, ref items } => {
+                    for item in items {
+                        here += match *item {
+                            Datum::Bytes(ref bytes) => bytes.len() * width,
+                            Datum::Value(_) => width,
+                        };
+                    }
+                }
+                Kind::Instruction { ref operands, .. } => {
+                    let long = operands.iter().any(|op| matches!(op, Operand::Long(_)));
+                    here += if long { 4 } else { 2 };
+                }
+            }
+            if here > 0x10000 {
+                return Err(error("program does not fit in 64K".to_string()));
+            }
+        }
+
+        let mut rom = vec![];
+        for (statement, &addr) in statements.iter().zip(addresses.iter()) {
+            let bytes = match statement.kind {
+                Kind::Data { width, ref items } => self.data(width, items, addr),
+                Kind::Instruction {
+                    ref mnemonic,
+                    ref operands,
+                } => self.instruction(mnemonic, operands, addr),
+                _ => continue,
+            };
+            let bytes = bytes.map_err(|e| statement.error(e))?;
+            rom.resize(addr - PROGRAM_START, 0); //fills an ORG gap
+            rom.extend_from_slice(&bytes);
+        }
+
+        Ok(Assembly {
+            rom,
+            labels: self.labels.into_iter().collect(),
+        })
+    }
+
+    fn defined(&self, name: &str) -> bool {
+        self.labels.contains_key(name) || self.constants.contains_key(name)
+    }
+
+    fn data(&self, width: usize, items: &[Datum], here: usize) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        for item in items {
+            match *item {
+                Datum::Bytes(ref s) if width == 1 => bytes.extend_from_slice(s),
+                Datum::Bytes(ref s) => s.iter().for_each(|c| bytes.extend_from_slice(&[0, *c])),
+                Datum::Value(ref expr) if width == 1 => {
+                    bytes.push(self.ranged(expr, here, -0x80, 0xFF, "byte")? as u8)
+                }
+                Datum::Value(ref expr) => {
+                    let word = self.ranged(expr, here, -0x8000, 0xFFFF, "word")? as u16;
+                    bytes.extend_from_slice(&[(word >> 8) as u8, word as u8]);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Evaluates `expr`, checking it lies in `min..=max`.
+    fn ranged(
+        &self,
+        expr: &Expr,
+        here: usize,
+        min: i64,
+        max: i64,
+        what: &str,
+    ) -> Result<i64, String> {
+        let value = self.eval(expr, here)?;
+        if value < min || value > max {
+            return Err(format!("{} {} is out of range", what, value));
+        }
+        Ok(value & max)
+    }
+
+    fn instruction(
+        &self,
+        mnemonic: &str,
+        operands: &[Operand],
+        here: usize,
+    ) -> Result<Vec<u8>, String> {
+        use self::Operand::*;
+        use instructions::Instructions::*;
+
+        //`encode` checks addresses and nibbles fit their fields
+        let addr = |e: &Expr| self.ranged(e, here, 0, 0xFFFF, "address").map(|v| v as u16);
+        let byte = |e: &Expr| self.ranged(e, here, -0x80, 0xFF, "byte").map(|v| v as u8);
+        let nibble = |e: &Expr| self.ranged(e, here, 0, 0xFF, "nibble").map(|v| v as u8);
+
+        let instruction = match (mnemonic, operands) {
+            ("CLS", []) => ClearScreen,
+            ("RET", []) => Return,
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => LowRes,
+            ("HIGH", []) => HighRes,
+            ("SCD", [Value(n)]) => ScrollDown(nibble(n)?),
+            ("SCU", [Value(n)]) => ScrollUp(nibble(n)?),
+            ("JP", [Value(a)]) => JumpToAddress(addr(a)?),
+            ("JP", [V(0), Value(a)]) => JumpToValue { value: addr(a)? },
+            ("CALL", [Value(a)]) => CallSub(addr(a)?),
+            ("SE", [V(x), Value(b)]) => SkipIfEqual {
+                x: *x,
+                value: byte(b)?,
+            },
+            ("SNE", [V(x), Value(b)]) => SkipIfNotEqualValue {
+                x: *x,
+                value: byte(b)?,
+            },
+            ("SE", [V(x), V(y)]) => SkipIfRegEqual { x: *x, y: *y },
+            ("SAVE", [V(x), V(y)]) => SaveRange { x: *x, y: *y },
+            ("LOAD", [V(x), V(y)]) => LoadRange { x: *x, y: *y },
+            ("LD", [V(x), Value(b)]) => SetValueToReg {
+                x: *x,
+                value: byte(b)?,
+            },
+            ("ADD", [V(x), Value(b)]) => AddValueToReg {
+                x: *x,
+                value: byte(b)?,
+            },
+            ("LD", [V(x), V(y)]) => AssignValueToReg { x: *x, y: *y },
+            ("OR", [V(x), V(y)]) => AssignOrValue { x: *x, y: *y },
+            ("AND", [V(x), V(y)]) => AssignAndValue { x: *x, y: *y },
+            ("XOR", [V(x), V(y)]) => AssignXorValue { x: *x, y: *y },
+            ("ADD", [V(x), V(y)]) => AssignAddValue { x: *x, y: *y },
+            ("SUB", [V(x), V(y)]) => AssignSubValue { x: *x, y: *y },
+            ("SHR", [V(x), V(y)]) => ShiftRight { x: *x, y: *y },
+            ("SHR", [V(x)]) => ShiftRight { x: *x, y: *x },
+            ("SUBN", [V(x), V(y)]) => AssignMinusValue { x: *x, y: *y },
+            ("SHL", [V(x), V(y)]) => ShiftLeft { x: *x, y: *y },
+            ("SHL", [V(x)]) => ShiftLeft { x: *x, y: *x },
+            ("SNE", [V(x), V(y)]) => SkipIfRegNotEqual { x: *x, y: *y },
+            ("LD", [I, Value(a)]) => SetMem { value: addr(a)? },
+            ("LD", [I, Long(a)]) => {
+                let a = addr(a)?;
+                return Ok(vec![0xF0, 0x00, (a >> 8) as u8, a as u8]);
+            }
+            ("RND", [V(x), Value(b)]) => RandomAnd {
+                x: *x,
+                value: byte(b)?,
+            },
+            ("DRW", [V(x), V(y), Value(n)]) => Display {
+                x: *x,
+                y: *y,
+                value: nibble(n)?,
+            },
+            ("SKP", [V(x)]) => PressedKey { x: *x },
+            ("SKNP", [V(x)]) => NotPressedKey { x: *x },
+            ("PLANE", [Value(n)]) => SelectPlane(nibble(n)?),
+            ("AUDIO", []) => AudioPattern,
+            ("LD", [V(x), DT]) => SetValueToDelayTimer { x: *x },
+            ("LD", [V(x), K]) => WaitForKey { x: *x },
+            ("LD", [DT, V(x)]) => SetDelayTimerToReg { x: *x },
+            ("LD", [ST, V(x)]) => SetSoundTimerTOReg { x: *x },
+            ("ADD", [I, V(x)]) => SetIFromReg { x: *x },
+            ("LD", [F, V(x)]) => SetIFromSprite { x: *x },
+            ("LD", [HF, V(x)]) => SetIFromBigSprite { x: *x },
+            ("LD", [B, V(x)]) => BCD { x: *x },
+            ("PITCH", [V(x)]) => SetPitch { x: *x },
+            ("LD", [IndirectI, V(x)]) => RegDump { x: *x },
+            ("LD", [V(x), IndirectI]) => RegLoad { x: *x },
+            ("LD", [R, V(x)]) => SaveFlags { x: *x },
+            ("LD", [V(x), R]) => LoadFlags { x: *x },
+            _ => return Err(format!("no {} instruction takes these operands", mnemonic)),
+        };
+        let opcode = instruction.encode().map_err(|e| e.to_string())?;
+        Ok(vec![(opcode >> 8) as u8, opcode as u8])
+    }
+}
+
+impl Statement {
+    fn error(&self, message: String) -> AsmError {
+        AsmError {
+            file: self.file.to_string(),
+            line: self.line,
+            message,
+        }
+    }
+}
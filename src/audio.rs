@@ -0,0 +1,209 @@
+//! Beeper synthesis shared by the audio backends.
+
+use std::f32::consts::PI;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use cpu::{CPU, DEFAULT_PITCH};
+use frontend::{Audio, FRAME_RATE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Waveform, String> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" => Ok(Waveform::Sawtooth),
+            "sine" => Ok(Waveform::Sine),
+            _ => Err(format!("unknown waveform {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioConfig {
+    pub frequency: f32, //Hz, for ROMs without an XO-CHIP audio pattern
+
+    pub volume: f32, //0.0 to 1.0
+
+    pub waveform: Waveform,
+
+    pub muted: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+            muted: false,
+        }
+    }
+}
+
+/// What the beeper should play, sampled from the CPU once per frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Beeper {
+    pub on: bool, //sound timer is running
+
+    pub pattern: Option<[u8; 16]>, //XO-CHIP 1-bit sample loop, played instead of the tone
+
+    pub pitch: u8, //XO-CHIP pattern playback rate
+}
+
+impl Beeper {
+    pub fn from_cpu(cpu: &CPU) -> Beeper {
+        Beeper {
+            on: cpu.sound_timer > 0,
+            pattern: cpu.audio_pattern,
+            pitch: cpu.pitch,
+        }
+    }
+}
+
+impl Default for Beeper {
+    fn default() -> Beeper {
+        Beeper {
+            on: false,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+/// Generates beeper samples in the range -1.0 to 1.0.
+pub struct ToneGenerator {
+    pub config: AudioConfig,
+
+    pub beeper: Beeper,
+
+    sample_rate: u32,
+
+    phase: f32, //0.0 to 1.0 through a tone period, or bits into the pattern
+}
+
+impl ToneGenerator {
+    pub fn new(config: AudioConfig, sample_rate: u32) -> ToneGenerator {
+        ToneGenerator {
+            config,
+            beeper: Beeper::default(),
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if !self.beeper.on || self.config.muted {
+            return 0.0;
+        }
+
+        let value = match self.beeper.pattern {
+            Some(pattern) => {
+                //pitch 64 plays 4000 bits per second, 48 steps per octave
+                let rate = 4000.0 * 2_f32.powf((self.beeper.pitch as f32 - 64.0) / 48.0);
+                let bit = self.phase as usize % 128;
+                self.phase = (self.phase + rate / self.sample_rate as f32) % 128.0;
+                if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            None => {
+                let phase = self.phase;
+                self.phase = (self.phase + self.config.frequency / self.sample_rate as f32) % 1.0;
+                match self.config.waveform {
+                    Waveform::Square => {
+                        if phase < 0.5 {
+                            1.0
+                        } else {
+                            -1.0
+                        }
+                    }
+                    Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                    Waveform::Sawtooth => 2.0 * phase - 1.0,
+                    Waveform::Sine => (2.0 * PI * phase).sin(),
+                }
+            }
+        };
+
+        value * self.config.volume
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+}
+
+/// An `Audio` sink that renders the beeper into memory instead of playing it,
+/// one frame's worth of samples per update.
+pub struct WavRecorder {
+    pub samples: Vec<i16>,
+
+    generator: ToneGenerator,
+
+    sample_rate: u32,
+}
+
+impl WavRecorder {
+    pub fn new(config: AudioConfig, sample_rate: u32) -> WavRecorder {
+        WavRecorder {
+            samples: vec![],
+            generator: ToneGenerator::new(config, sample_rate),
+            sample_rate,
+        }
+    }
+
+    /// Writes the recording as a 16-bit mono PCM WAV file.
+    pub fn write_wav<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let data_len = self.samples.len() as u32 * 2;
+
+        out.write_all(b"RIFF")?;
+        out.write_u32::<LittleEndian>(36 + data_len)?;
+        out.write_all(b"WAVE")?;
+
+        out.write_all(b"fmt ")?;
+        out.write_u32::<LittleEndian>(16)?; //chunk size
+        out.write_u16::<LittleEndian>(1)?; //PCM
+        out.write_u16::<LittleEndian>(1)?; //mono
+        out.write_u32::<LittleEndian>(self.sample_rate)?;
+        out.write_u32::<LittleEndian>(self.sample_rate * 2)?; //byte rate
+        out.write_u16::<LittleEndian>(2)?; //block align
+        out.write_u16::<LittleEndian>(16)?; //bits per sample
+
+        out.write_all(b"data")?;
+        out.write_u32::<LittleEndian>(data_len)?;
+        for sample in &self.samples {
+            out.write_i16::<LittleEndian>(*sample)?;
+        }
+        Ok(())
+    }
+}
+
+impl Audio for WavRecorder {
+    fn update(&mut self, beeper: &Beeper) {
+        self.generator.beeper = *beeper;
+        for _ in 0..self.sample_rate / FRAME_RATE {
+            let sample = self.generator.next_sample();
+            self.samples.push((sample * i16::MAX as f32) as i16);
+        }
+    }
+
+    fn toggle_mute(&mut self) {
+        self.generator.config.muted = !self.generator.config.muted;
+    }
+}
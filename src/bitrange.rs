@@ -52,7 +52,7 @@ impl BitRange for u16 {
 
         let mask = 2_u16.pow(num_bits as u32) - 1;
 
-        ((self >> range.start) & mask) as u16
+        (self >> range.start) & mask
     }
 }
 
@@ -64,7 +64,7 @@ impl BitRange for u8 {
         assert!(num_bits < 8);
 
         let mask = 2_u8.pow(num_bits as u32) - 1;
-        ((self >> range.start) & mask) as u8
+        (self >> range.start) & mask
     }
 
     fn range_u16(&self, range: Range<usize>) -> u16 {
@@ -0,0 +1,201 @@
+//! A coverage map of the program's memory: which bytes ran as instructions,
+//! were read through I (sprites, FX65 and the like) or were written.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use cpu::PROGRAM_START;
+
+pub const EXECUTED: u8 = 1;
+pub const READ: u8 = 2;
+pub const WRITTEN: u8 = 4;
+
+/// Access flags by address, for `CPU::coverage`.
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    /// A map for a machine with `memory_size` bytes of memory.
+    pub fn new(memory_size: usize) -> Coverage {
+        Coverage {
+            flags: vec![0; memory_size],
+        }
+    }
+
+    /// Marks `len` bytes at `address` with `flag`.
+    pub fn mark(&mut self, address: usize, len: usize, flag: u8) {
+        let end = (address + len).min(self.flags.len());
+        for f in &mut self.flags[address.min(end)..end] {
+            *f |= flag;
+        }
+    }
+
+    /// The flags of the byte at `address`.
+    pub fn flags(&self, address: usize) -> u8 {
+        self.flags.get(address).cloned().unwrap_or(0)
+    }
+
+    /// Where reports stop: the end of the ROM or the last byte touched past
+    /// it, whichever is later.
+    fn end(&self, rom_len: usize) -> usize {
+        let touched = self
+            .flags
+            .iter()
+            .rposition(|&f| f != 0)
+            .map_or(0, |a| a + 1);
+        (PROGRAM_START + rom_len).max(touched).min(self.flags.len())
+    }
+
+    /// Summarises the map from `PROGRAM_START` for a ROM of `rom_len` bytes.
+    pub fn report(&self, rom_len: usize) -> Report {
+        let (start, end) = (PROGRAM_START, self.end(rom_len));
+        let count = |flag: u8| {
+            self.flags[start..end]
+                .iter()
+                .filter(|&&f| f & flag != 0)
+                .count()
+        };
+
+        let mut regions: Vec<Region> = vec![];
+        for address in start..end {
+            let flags = self.flags[address];
+            match regions.last_mut() {
+                Some(region) if region.flags == flags => region.end = address + 1,
+                _ => regions.push(Region {
+                    start: address,
+                    end: address + 1,
+                    flags,
+                }),
+            }
+        }
+
+        Report {
+            start,
+            end,
+            executed: count(EXECUTED),
+            read: count(READ),
+            written: count(WRITTEN),
+            untouched: self.flags[start..end].iter().filter(|&&f| f == 0).count(),
+            regions,
+        }
+    }
+
+    /// Writes a hex dump of `mem` from `PROGRAM_START` with each byte's
+    /// access marked alongside.
+    pub fn write_hexdump<W: Write>(
+        &self,
+        mem: &[u8],
+        rom_len: usize,
+        mut out: W,
+    ) -> io::Result<()> {
+        writeln!(
+            out,
+            "; x executed, r read via I, w written, X executed and read or written,"
+        )?;
+        writeln!(out, "; m read and written, . untouched")?;
+        let end = self.end(rom_len).min(mem.len());
+        for row in (PROGRAM_START..end).step_by(16) {
+            let row_end = (row + 16).min(end);
+            let bytes: Vec<String> = mem[row..row_end]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            let marks: String = (row..row_end).map(|a| mark(self.flags[a])).collect();
+            writeln!(out, "{:04X}  {:<47}  {}", row, bytes.join(" "), marks)?;
+        }
+        Ok(())
+    }
+}
+
+fn mark(flags: u8) -> char {
+    match flags {
+        0 => '.',
+        EXECUTED => 'x',
+        READ => 'r',
+        WRITTEN => 'w',
+        f if f & EXECUTED != 0 => 'X',
+        _ => 'm',
+    }
+}
+
+/// A run of bytes with the same flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: usize,
+
+    pub end: usize, //exclusive
+
+    pub flags: u8,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = [(EXECUTED, "executed"), (READ, "read"), (WRITTEN, "written")]
+            .iter()
+            .filter(|&&(flag, _)| self.flags & flag != 0)
+            .map(|&(_, name)| name)
+            .collect();
+        let kind = if names.is_empty() {
+            "untouched".to_string()
+        } else {
+            names.join(", ")
+        };
+        write!(
+            f,
+            "{:04X}-{:04X}  {:>5}  {}",
+            self.start,
+            self.end - 1,
+            self.end - self.start,
+            kind
+        )
+    }
+}
+
+/// What `Coverage::report` found. `Display` gives the text report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub start: usize,
+
+    pub end: usize, //exclusive
+
+    pub executed: usize, //bytes
+
+    pub read: usize,
+
+    pub written: usize,
+
+    pub untouched: usize,
+
+    pub regions: Vec<Region>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.end - self.start;
+        let percent = |n: usize| match total {
+            0 => 0.0,
+            total => n as f64 * 100.0 / total as f64,
+        };
+        writeln!(
+            f,
+            "{:04X}-{:04X}, {} bytes",
+            self.start,
+            self.end.max(self.start + 1) - 1,
+            total
+        )?;
+        for &(name, n) in &[
+            ("executed", self.executed),
+            ("read", self.read),
+            ("written", self.written),
+            ("untouched", self.untouched),
+        ] {
+            writeln!(f, "  {:<10} {:>5}  {:5.1}%", name, n, percent(n))?;
+        }
+        writeln!(f, "\nregions (first-last, bytes, access):")?;
+        for region in &self.regions {
+            writeln!(f, "  {}", region)?;
+        }
+        Ok(())
+    }
+}
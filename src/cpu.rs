@@ -1,16 +1,25 @@
-extern crate rand;
-
+use std::error;
+use std::fmt;
 use std::fs::File;
-use std::io::{Cursor, Error, Read, Write};
-use std::time::Duration;
+use std::io::{self, Cursor, Read};
 
 use byteorder::{BigEndian, ReadBytesExt};
 
-use gpu::*;
+use coverage::{self, Coverage};
+use frontend::{Timing, FRAME_RATE};
+use gpu::GPU;
 use instructions::Instructions;
+use profile::Profiler;
+use quirks::{Platform, Quirks};
+use rng::Rng;
+use trace::Tracer;
 
 //const
-pub const CPU_FREQ: Duration = Duration::from_millis(2);
+pub const STACK_SIZE: usize = 16;
+pub const MEMORY_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200;
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - PROGRAM_START; //3584 bytes, more on XO-CHIP
+pub const DEFAULT_PITCH: u8 = 64; //4000 Hz pattern playback
 const FONTSET: [u8; 80] = [
     0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -29,7 +38,203 @@ const FONTSET: [u8; 80] = [
     0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
     0xf0, 0x80, 0xf0, 0x80, 0x80, // F
 ];
+pub const BIG_FONT_START: usize = 0x50; //SUPER-CHIP 8x10 digits, right after FONTSET
+const BIG_FONTSET: [u8; 160] = [
+    0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xff, 0xff, // 1
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // 2
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 3
+    0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0x03, 0x03, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 5
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 6
+    0xff, 0xff, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x18, 0x18, 0x18, // 7
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 8
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 9
+    0x7e, 0xff, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, // A
+    0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, // B
+    0x3c, 0xff, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0xff, 0x3c, // C
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // D
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // E
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0, // F
+];
+
+/// A ROM that cannot be loaded.
+#[derive(Debug)]
+pub enum RomError {
+    Empty,
+
+    TooLarge { size: usize, max: usize },
+
+    Io(io::Error), //the ROM could not be read
+}
+
+impl From<io::Error> for RomError {
+    fn from(error: io::Error) -> Self {
+        RomError::Io(error)
+    }
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::Empty => write!(f, "ROM is empty"),
+            RomError::TooLarge { size, max } => {
+                write!(
+                    f,
+                    "ROM is {} bytes, larger than the {} bytes available",
+                    size, max
+                )
+            }
+            RomError::Io(ref e) => write!(f, "cannot read ROM: {}", e),
+        }
+    }
+}
+
+impl error::Error for RomError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            RomError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A fault raised by the running program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    InvalidOpcode {
+        pc: usize,
+        opcode: u16,
+    },
+
+    StackUnderflow {
+        pc: usize,
+        opcode: u16,
+    }, //00EE with an empty stack
+
+    StackOverflow {
+        pc: usize,
+        opcode: u16,
+    }, //2NNN with STACK_SIZE return addresses
+
+    PcOutOfBounds {
+        pc: usize,
+    }, //no full opcode at pc
+
+    IndexOutOfBounds {
+        pc: usize,
+        opcode: u16,
+        address: usize,
+    }, //memory access through I
+
+    InvalidKey {
+        pc: usize,
+        opcode: u16,
+        key: u8,
+    }, //EX9E/EXA1 with Vx > 0xF
+}
+
+impl CpuError {
+    pub fn pc(&self) -> usize {
+        match *self {
+            CpuError::InvalidOpcode { pc, .. }
+            | CpuError::StackUnderflow { pc, .. }
+            | CpuError::StackOverflow { pc, .. }
+            | CpuError::PcOutOfBounds { pc }
+            | CpuError::IndexOutOfBounds { pc, .. }
+            | CpuError::InvalidKey { pc, .. } => pc,
+        }
+    }
+
+    pub fn opcode(&self) -> Option<u16> {
+        match *self {
+            CpuError::InvalidOpcode { opcode, .. }
+            | CpuError::StackUnderflow { opcode, .. }
+            | CpuError::StackOverflow { opcode, .. }
+            | CpuError::IndexOutOfBounds { opcode, .. }
+            | CpuError::InvalidKey { opcode, .. } => Some(opcode),
+            CpuError::PcOutOfBounds { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpuError::InvalidOpcode { .. } => write!(f, "invalid opcode")?,
+            CpuError::StackUnderflow { .. } => write!(f, "return with an empty stack")?,
+            CpuError::StackOverflow { .. } => write!(f, "stack overflow")?,
+            CpuError::PcOutOfBounds { .. } => write!(f, "program counter out of memory")?,
+            CpuError::IndexOutOfBounds { address, .. } => {
+                write!(f, "I points outside memory ({:04X})", address)?
+            }
+            CpuError::InvalidKey { key, .. } => write!(f, "invalid key {:02X}", key)?,
+        }
+        match self.opcode() {
+            Some(opcode) => write!(f, " at {:03X} (opcode {:04X})", self.pc(), opcode),
+            None => write!(f, " at {:03X}", self.pc()),
+        }
+    }
+}
+
+impl error::Error for CpuError {}
+
+/// What `emulate_cycle` does when the program faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAction {
+    Halt, //return the error and leave the machine untouched, pc still on the faulting instruction
+    Skip, //move past the faulting instruction, then return the error
+    Nop,  //move past the faulting instruction as if it were a no-op and carry on
+}
+
+/// The `FaultAction` for each kind of `CpuError`. Defaults to halting on all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorPolicy {
+    pub invalid_opcode: FaultAction,
+    pub stack_underflow: FaultAction,
+    pub stack_overflow: FaultAction,
+    pub pc_out_of_bounds: FaultAction, //skipping wraps pc around memory
+    pub index_out_of_bounds: FaultAction,
+    pub invalid_key: FaultAction,
+}
 
+impl ErrorPolicy {
+    pub fn all(action: FaultAction) -> ErrorPolicy {
+        ErrorPolicy {
+            invalid_opcode: action,
+            stack_underflow: action,
+            stack_overflow: action,
+            pc_out_of_bounds: action,
+            index_out_of_bounds: action,
+            invalid_key: action,
+        }
+    }
+
+    pub fn action(&self, error: &CpuError) -> FaultAction {
+        match *error {
+            CpuError::InvalidOpcode { .. } => self.invalid_opcode,
+            CpuError::StackUnderflow { .. } => self.stack_underflow,
+            CpuError::StackOverflow { .. } => self.stack_overflow,
+            CpuError::PcOutOfBounds { .. } => self.pc_out_of_bounds,
+            CpuError::IndexOutOfBounds { .. } => self.index_out_of_bounds,
+            CpuError::InvalidKey { .. } => self.invalid_key,
+        }
+    }
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> ErrorPolicy {
+        ErrorPolicy::all(FaultAction::Halt)
+    }
+}
+
+/// A CHIP-8 machine.
+///
+/// `run_frame` runs a frame at a time and ends each with a vertical blank.
+/// A frontend that calls `emulate_cycle` itself must also call `end_frame`
+/// once per frame. The default platform, the COSMAC VIP, sets
+/// `Quirks::display_wait`, so DXYN waits for a vblank, and a loop of bare
+/// `emulate_cycle` calls stops at the first draw.
 pub struct CPU {
     pub mem: Cursor<Vec<u8>>, //4096 bytes
 
@@ -48,29 +253,82 @@ pub struct CPU {
 
     pub keys: [u8; 16], //keyboard
 
+    pub rpl: [u8; 16], //SUPER-CHIP RPL user flags (FX75/FX85)
+
+    pub halted: bool, //set by 00FD
+
+    pub audio_pattern: Option<[u8; 16]>, //XO-CHIP 1-bit sample loop (F002), a plain tone if unset
+
+    pub pitch: u8, //XO-CHIP pattern playback rate (FX3A)
+
+    pub platform: Platform,
+
     pub gpu: GPU, //gpu
+
+    pub error_policy: ErrorPolicy, //what to do when the program faults
+
+    pub quirks: Quirks, //interpreter behaviours the ROM expects
+
+    pub vblank: bool, //set by the frontend each frame, consumed by DXYN under Quirks::display_wait
+
+    pub rng: Rng, //CXNN random numbers, seeded from the OS unless replaced
+
+    pub cycles: u64, //instructions fetched since the CPU was created
+
+    pub tracer: Option<Tracer>, //logs each instruction as it is fetched
+
+    pub profiler: Option<Profiler>, //counts executions by address and kind
+
+    pub coverage: Option<Coverage>, //marks memory as executed, read or written
+
+    cycle_acc: u32, //Timing::clock_hz left over from the last run_frame
+
+    timer_acc: u32, //Timing::timer_hz likewise
 }
 
 impl CPU {
-    pub fn new(rom_path: &str, gpu: GPU) -> Result<Self, Error> {
-        let mut mem = [0_u8; 4096];
+    /// Loads the ROM at `rom_path`.
+    pub fn new(rom_path: &str) -> Result<Self, RomError> {
+        CPU::from_reader(File::open(rom_path)?)
+    }
+
+    /// Loads a ROM from any reader, e.g. an archive entry or a network stream.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, RomError> {
+        let mut buf: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        CPU::from_bytes(&buf)
+    }
+
+    /// Loads a ROM image already in memory.
+    pub fn from_bytes(rom: &[u8]) -> Result<Self, RomError> {
+        CPU::with_platform(rom, Platform::default())
+    }
+
+    /// Loads a ROM image for `platform`, which sets the memory size and quirks.
+    pub fn with_platform(rom: &[u8], platform: Platform) -> Result<Self, RomError> {
+        let max = platform.memory_size() - PROGRAM_START;
+        if rom.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if rom.len() > max {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+
+        let mut mem = vec![0_u8; platform.memory_size()];
 
         //load font set
-        (0..80).for_each(|i| {
-            mem[0x0 + i] = FONTSET[i];
-        });
+        mem[..FONTSET.len()].copy_from_slice(&FONTSET);
+        mem[BIG_FONT_START..BIG_FONT_START + BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
 
         //load rom data
-        let mut file = File::open(rom_path)?;
-        let mut buf: Vec<u8> = Vec::new();
-        file.read_to_end(&mut buf)?;
-        for (i, b) in buf.iter().enumerate() {
-            mem[i + 0x200] = *b;
-        }
+        mem[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
 
         Ok(CPU {
-            pc: 0x200, //pc start point
-            mem: Cursor::new(mem.to_vec()),
+            pc: PROGRAM_START, //pc start point
+            mem: Cursor::new(mem),
             index_reg: 0,
             registers: [0_u8; 16],
             delay_timer: 0,
@@ -78,29 +336,186 @@ impl CPU {
             stack: vec![],
             sp: 0,
             keys: [0_u8; 16],
-            gpu,
+            rpl: [0_u8; 16],
+            halted: false,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            platform,
+            gpu: GPU::new(),
+            error_policy: ErrorPolicy::default(),
+            quirks: platform.quirks(),
+            vblank: false,
+            rng: Rng::from_entropy(),
+            cycles: 0,
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            cycle_acc: 0,
+            timer_acc: 0,
         })
     }
 
-    pub fn emulate_cycle(&mut self) {
+    /// Counts the delay and sound timers down by one. Call this at 60 Hz,
+    /// independently of how fast instructions execute.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    /// Runs one frame's worth of instructions and timer ticks at `timing`,
+    /// then the vertical blank. Stops early if the program exits or hits a
+    /// fault its `ErrorPolicy` halts on; the faults it skipped are returned.
+    pub fn run_frame(&mut self, timing: Timing) -> Result<Vec<CpuError>, CpuError> {
+        //rates rarely divide evenly by FRAME_RATE, so carry the remainders over
+        let mut skipped = vec![];
+        self.cycle_acc += timing.clock_hz;
+        for _ in 0..self.cycle_acc / FRAME_RATE {
+            if self.halted {
+                break;
+            }
+            if let Err(e) = self.emulate_cycle() {
+                if self.error_policy.action(&e) == FaultAction::Halt {
+                    return Err(e);
+                }
+                skipped.push(e);
+            }
+        }
+        self.cycle_acc %= FRAME_RATE;
+
+        self.timer_acc += timing.timer_hz;
+        for _ in 0..self.timer_acc / FRAME_RATE {
+            self.tick_timers();
+        }
+        self.timer_acc %= FRAME_RATE;
+
+        self.end_frame();
+        Ok(skipped)
+    }
+
+    /// Signals the vertical blank that ends a frame.
+    pub fn end_frame(&mut self) {
+        self.vblank = true;
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.end_frame();
+        }
+    }
+
+    /// Executes one instruction, applying `error_policy` if it faults. Under
+    /// `Quirks::display_wait`, DXYN leaves the PC where it is until
+    /// `end_frame` signals the next vblank.
+    pub fn emulate_cycle(&mut self) -> Result<(), CpuError> {
+        let error = match self.step() {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        match self.error_policy.action(&error) {
+            FaultAction::Halt => Err(error),
+            FaultAction::Skip => {
+                self.skip_fault(&error);
+                Err(error)
+            }
+            FaultAction::Nop => {
+                self.skip_fault(&error);
+                Ok(())
+            }
+        }
+    }
+
+    fn skip_fault(&mut self, error: &CpuError) {
+        match *error {
+            CpuError::PcOutOfBounds { pc } => self.pc = (pc + 2) % self.mem.get_ref().len(),
+            _ => self.increase_pc(),
+        }
+    }
+
+    fn step(&mut self) -> Result<(), CpuError> {
+        if self.halted {
+            return Ok(());
+        }
+
+        let pc = self.pc;
+
         //fetch
-        let instruction: Instructions = match self.fetch_opcode() {
+        let opcode = match self.fetch_opcode() {
             Ok(code) => code,
-            Err(_) => panic!("fail to fetch opcode from memory"),
-        }.into();
+            Err(_) => return Err(CpuError::PcOutOfBounds { pc }),
+        };
+        self.cycles += 1;
+        if let Some(mut tracer) = self.tracer.take() {
+            tracer.trace(self, opcode);
+            self.tracer = Some(tracer);
+        }
+
+        //decode
+        let instruction = match Instructions::decode(opcode, pc) {
+            Ok(instruction) => instruction,
+            Err(_) => return Err(CpuError::InvalidOpcode { pc, opcode }),
+        };
+
+        self.check(instruction, opcode)?;
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.count(pc, instruction, &self.stack, self.mem.get_ref());
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            let len = if instruction == Instructions::LongIndex {
+                4
+            } else {
+                2
+            };
+            coverage.mark(pc, len, coverage::EXECUTED);
+        }
 
         //execute
         match instruction {
+            Instructions::ScrollDown(rows) => {
+                self.gpu.scroll_down(rows as usize);
+                self.increase_pc();
+            }
+
+            Instructions::ScrollUp(rows) => {
+                self.gpu.scroll_up(rows as usize);
+                self.increase_pc();
+            }
+
             Instructions::ClearScreen => {
-                for i in 0..2048 {
-                    self.gpu.gfx[i] = 0;
-                }
+                self.gpu.clear();
+                self.increase_pc();
+            }
+
+            Instructions::ScrollRight => {
+                self.gpu.scroll_right(4);
+                self.increase_pc();
+            }
+
+            Instructions::ScrollLeft => {
+                self.gpu.scroll_left(4);
+                self.increase_pc();
+            }
+
+            Instructions::Exit => {
+                self.halted = true;
+            }
+
+            Instructions::LowRes => {
+                self.gpu.set_hires(false);
+                self.increase_pc();
+            }
+
+            Instructions::HighRes => {
+                self.gpu.set_hires(true);
                 self.increase_pc();
             }
 
             Instructions::Return => {
                 if let Some(rv) = self.stack.pop() {
-                    self.pc = rv as usize;
+                    self.sp = self.stack.len();
+                    self.pc = rv;
                     self.increase_pc();
                 }
             }
@@ -111,6 +526,7 @@ impl CPU {
 
             Instructions::CallSub(address) => {
                 self.stack.push(self.pc);
+                self.sp = self.stack.len();
                 self.pc = address as usize;
             }
 
@@ -138,6 +554,22 @@ impl CPU {
                 }
             }
 
+            Instructions::SaveRange { x, y } => {
+                let i = self.index_reg as usize;
+                for (n, r) in register_range(x, y).into_iter().enumerate() {
+                    self.write_mem(i + n, self.registers[r]);
+                }
+                self.increase_pc();
+            }
+
+            Instructions::LoadRange { x, y } => {
+                let i = self.index_reg as usize;
+                for (n, r) in register_range(x, y).into_iter().enumerate() {
+                    self.registers[r] = self.read_mem(i + n, 1)[0];
+                }
+                self.increase_pc();
+            }
+
             Instructions::SetValueToReg { x, value } => {
                 self.registers[x as usize] = value;
                 self.increase_pc();
@@ -155,16 +587,25 @@ impl CPU {
 
             Instructions::AssignOrValue { x, y } => {
                 self.registers[x as usize] |= self.registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xF] = 0;
+                }
                 self.increase_pc();
             }
 
             Instructions::AssignAndValue { x, y } => {
                 self.registers[x as usize] &= self.registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xF] = 0;
+                }
                 self.increase_pc();
             }
 
             Instructions::AssignXorValue { x, y } => {
                 self.registers[x as usize] ^= self.registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xF] = 0;
+                }
                 self.increase_pc();
             }
 
@@ -172,7 +613,7 @@ impl CPU {
                 let vx = self.registers[x as usize];
                 let vy = self.registers[y as usize];
                 self.registers[x as usize] = vx.wrapping_add(vy);
-                self.registers[0xF] = ((x as u16) + (y as u16) > 255) as u8;
+                self.registers[0xF] = ((vx as u16) + (vy as u16) > 255) as u8;
                 self.increase_pc();
             }
 
@@ -184,10 +625,10 @@ impl CPU {
                 self.increase_pc();
             }
 
-            Instructions::ShiftRight { x } => {
-                let vx = self.registers[x as usize];
-                self.registers[x as usize] = vx >> 1;
-                self.registers[0xF] = vx & 1;
+            Instructions::ShiftRight { x, y } => {
+                let v = self.shift_source(x, y);
+                self.registers[x as usize] = v >> 1;
+                self.registers[0xF] = v & 1;
                 self.increase_pc();
             }
 
@@ -200,10 +641,10 @@ impl CPU {
                 self.increase_pc();
             }
 
-            Instructions::ShiftLeft { x } => {
-                let vx = self.registers[x as usize];
-                self.registers[x as usize] = vx << 1;
-                self.registers[0xF] = vx >> 7;
+            Instructions::ShiftLeft { x, y } => {
+                let v = self.shift_source(x, y);
+                self.registers[x as usize] = v << 1;
+                self.registers[0xF] = v >> 7;
                 self.increase_pc();
             }
 
@@ -221,43 +662,43 @@ impl CPU {
             }
 
             Instructions::JumpToValue { value } => {
-                self.pc = (value + self.registers[0] as u16) as usize;
+                let offset = if self.quirks.jump_uses_vx {
+                    self.registers[((value >> 8) & 0xF) as usize]
+                } else {
+                    self.registers[0]
+                };
+                self.pc = (value + offset as u16) as usize;
             }
 
             Instructions::RandomAnd { x, value } => {
-                self.registers[x as usize] = rand::random::<u8>() & value;
+                self.registers[x as usize] = self.rng.next_u8() & value;
                 self.increase_pc();
             }
 
             Instructions::Display { x, y, value } => {
-                let mut vx = self.registers[x as usize];
-                let mut vy = self.registers[y as usize];
-                let height = value;
-
-                #[allow(unused_assignments)]
-                let mut pixel = 0;
-
-                self.registers[0xF] = 0;
-
-                for yline in 0..height {
-                    self.mem
-                        .set_position((self.index_reg + yline as u16) as u64);
-                    pixel = self.mem.read_u8().unwrap_or(0);
-                    for xline in 0..8 {
-                        if (pixel & (0x80 >> xline)) != 0 {
-                            if self.gpu.gfx[(vx as usize)
-                                                + (xline as usize)
-                                                + (vy as usize + yline as usize) * 64]
-                                == 1
-                            {
-                                self.registers[0xF] = 1;
-                            }
-                            self.gpu.gfx[(vx as usize)
-                                             + (xline as usize)
-                                             + (vy as usize + yline as usize) * 64] ^= 1;
-                        }
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        return Ok(());
                     }
+                    self.vblank = false;
                 }
+
+                //16x16 sprites (DXY0) have two bytes per row
+                let (wide, rows) = if value == 0 {
+                    (true, 16)
+                } else {
+                    (false, value as usize)
+                };
+                let len = if wide { rows * 2 } else { rows } * self.gpu.selected_planes();
+                let sprite = self.read_mem(self.index_reg as usize, len).to_vec();
+                let collision = self.gpu.draw(
+                    self.registers[x as usize] as usize,
+                    self.registers[y as usize] as usize,
+                    &sprite,
+                    wide,
+                    self.quirks.sprite_wrap,
+                );
+                self.registers[0xF] = collision as u8;
                 self.increase_pc();
             }
 
@@ -279,14 +720,33 @@ impl CPU {
                 }
             }
 
+            Instructions::LongIndex => {
+                self.mem.set_position(pc as u64 + 2);
+                self.index_reg = self.mem.read_u16::<BigEndian>().unwrap_or(0);
+                self.pc += 4;
+            }
+
+            Instructions::SelectPlane(planes) => {
+                self.gpu.planes = planes;
+                self.increase_pc();
+            }
+
+            Instructions::AudioPattern => {
+                let i = self.index_reg as usize;
+                let mut pattern = [0_u8; 16];
+                pattern.copy_from_slice(self.read_mem(i, 16));
+                self.audio_pattern = Some(pattern);
+                self.increase_pc();
+            }
+
             Instructions::SetValueToDelayTimer { x } => {
                 self.registers[x as usize] = self.delay_timer;
                 self.increase_pc();
             }
 
             Instructions::WaitForKey { x } => {
-                let key = self.registers[x as usize] as usize;
-                if self.keys[key] == 1 {
+                if let Some(key) = self.keys.iter().position(|k| *k == 1) {
+                    self.registers[x as usize] = key as u8;
                     self.increase_pc();
                 }
             }
@@ -302,12 +762,25 @@ impl CPU {
             }
 
             Instructions::SetIFromReg { x } => {
-                self.index_reg += self.registers[x as usize] as u16;
+                self.index_reg = self
+                    .index_reg
+                    .wrapping_add(self.registers[x as usize] as u16);
                 self.increase_pc();
             }
 
             Instructions::SetIFromSprite { x } => {
-                self.index_reg = (self.registers[x as usize] * 5) as u16;
+                self.index_reg = (self.registers[x as usize] & 0xF) as u16 * 5;
+                self.increase_pc();
+            }
+
+            Instructions::SetIFromBigSprite { x } => {
+                self.index_reg =
+                    (BIG_FONT_START + (self.registers[x as usize] & 0xF) as usize * 10) as u16;
+                self.increase_pc();
+            }
+
+            Instructions::SetPitch { x } => {
+                self.pitch = self.registers[x as usize];
                 self.increase_pc();
             }
 
@@ -317,57 +790,151 @@ impl CPU {
                 let h = vx / 100;
                 let t = (vx / 10) % 10;
                 let d = (vx % 100) % 10;
-                self.mem.set_position(self.index_reg as u64);
-                self.mem.write(&[h, t, d]).unwrap_or(0);
+                let i = self.index_reg as usize;
+                self.write_mem(i, h);
+                self.write_mem(i + 1, t);
+                self.write_mem(i + 2, d);
 
                 self.increase_pc();
             }
 
             Instructions::RegDump { x } => {
-                for i in 0..x + 1 {
-                    self.mem.set_position(self.index_reg as u64);
-                    self.mem.write(&[self.registers[i as usize]]).unwrap_or(0);
-                    self.index_reg += 1;
+                let start = self.index_reg as usize;
+                for i in 0..=x as usize {
+                    self.write_mem(start + i, self.registers[i]);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.index_reg = self.index_reg.wrapping_add(x as u16 + 1);
                 }
 
                 self.increase_pc();
             }
 
             Instructions::RegLoad { x } => {
-                for i in 0..x + 1 {
-                    self.mem.set_position((self.index_reg as u16) as u64);
-                    self.registers[i as usize] = self.mem.read_u8().unwrap_or(0);
-                    self.index_reg += 1;
+                let start = self.index_reg as usize;
+                for i in 0..=x as usize {
+                    self.registers[i] = self.read_mem(start + i, 1)[0];
+                }
+                if self.quirks.load_store_increments_i {
+                    self.index_reg = self.index_reg.wrapping_add(x as u16 + 1);
                 }
 
                 self.increase_pc();
             }
-        }
 
-        //timers
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+            Instructions::SaveFlags { x } => {
+                let n = x as usize + 1;
+                self.rpl[..n].copy_from_slice(&self.registers[..n]);
+                self.increase_pc();
+            }
+
+            Instructions::LoadFlags { x } => {
+                let n = x as usize + 1;
+                self.registers[..n].copy_from_slice(&self.rpl[..n]);
+                self.increase_pc();
+            }
         }
 
-        if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                //beep
-                // unimplemented!();
+        Ok(())
+    }
+
+    /// Reports the fault `instruction` would raise, before it changes anything.
+    fn check(&self, instruction: Instructions, opcode: u16) -> Result<(), CpuError> {
+        let pc = self.pc;
+        let i = self.index_reg as usize;
+
+        // number of bytes the instruction reads or writes starting at I
+        let span = match instruction {
+            Instructions::Return if self.stack.is_empty() => {
+                return Err(CpuError::StackUnderflow { pc, opcode })
             }
-            self.sound_timer -= 1;
+            Instructions::CallSub(_) if self.stack.len() >= STACK_SIZE => {
+                return Err(CpuError::StackOverflow { pc, opcode })
+            }
+            Instructions::PressedKey { x } | Instructions::NotPressedKey { x } => {
+                let key = self.registers[x as usize];
+                if key > 0xF {
+                    return Err(CpuError::InvalidKey { pc, opcode, key });
+                }
+                0
+            }
+            Instructions::LongIndex if pc + 4 > self.mem.get_ref().len() => {
+                return Err(CpuError::PcOutOfBounds { pc })
+            }
+            Instructions::Display { value: 0, .. } => 32 * self.gpu.selected_planes(),
+            Instructions::Display { value, .. } => value as usize * self.gpu.selected_planes(),
+            Instructions::SaveRange { x, y } | Instructions::LoadRange { x, y } => {
+                register_range(x, y).len()
+            }
+            Instructions::AudioPattern => 16,
+            Instructions::BCD { .. } => 3,
+            Instructions::RegDump { x } | Instructions::RegLoad { x } => x as usize + 1,
+            _ => 0,
+        };
+
+        let len = self.mem.get_ref().len();
+        if span > 0 && i + span > len {
+            let address = i.max(len);
+            return Err(CpuError::IndexOutOfBounds {
+                pc,
+                opcode,
+                address,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The `len` bytes at `address`, which `check` has already bounded.
+    fn read_mem(&mut self, address: usize, len: usize) -> &[u8] {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.mark(address, len, coverage::READ);
+        }
+        &self.mem.get_ref()[address..address + len]
+    }
+
+    /// Stores `value` at `address`, which `check` has already bounded.
+    fn write_mem(&mut self, address: usize, value: u8) {
+        self.mem.get_mut()[address] = value;
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.write(address, value);
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.mark(address, 1, coverage::WRITTEN);
         }
     }
 
-    pub fn fetch_opcode(&mut self) -> Result<u16, Error> {
+    pub fn fetch_opcode(&mut self) -> Result<u16, io::Error> {
         self.mem.set_position(self.pc as u64);
         self.mem.read_u16::<BigEndian>()
     }
 
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.registers[y as usize]
+        } else {
+            self.registers[x as usize]
+        }
+    }
+
     fn increase_pc(&mut self) {
         self.pc += 2;
     }
 
     fn skip_pc(&mut self) {
-        self.pc += 4;
+        //F000 NNNN is two words long, so its operand is skipped too
+        let next = self.pc + 2;
+        let long = self.mem.get_ref().get(next..next + 2) == Some(&[0xF0, 0x00][..]);
+        self.pc += if long { 6 } else { 4 };
+    }
+}
+
+/// Registers X through Y for 5XY2/5XY3, in descending order when X > Y.
+fn register_range(x: u8, y: u8) -> Vec<usize> {
+    let (x, y) = (x as usize, y as usize);
+    if x <= y {
+        (x..=y).collect()
+    } else {
+        (y..=x).rev().collect()
     }
 }
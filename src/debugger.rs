@@ -0,0 +1,272 @@
+//! An interactive debugger: breakpoints, stepping and inspecting or editing
+//! the machine. `Debugger::execute` runs one command line and returns the text
+//! to show, so any frontend can put a prompt in front of it.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use cpu::{CpuError, CPU};
+use disasm::{Labels, Line};
+use frontend::{Timing, FRAME_RATE};
+
+pub const HELP: &str = "\
+step [N]             execute N instructions (default 1)
+continue             run until a breakpoint, exit or fault
+until ADDR           run until the PC reaches ADDR
+break [ADDR]         set a breakpoint at ADDR, or list breakpoints
+delete ADDR          remove the breakpoint at ADDR
+regs                 show registers, I, the stack and timers
+inst                 show the instruction at the PC
+dump ADDR [LEN]      hex-dump LEN bytes of memory (default 64)
+write ADDR BYTE...   store bytes into memory
+help                 show this text
+quit                 leave the debugger
+Commands can be abbreviated to their first letter. Numbers are hex.";
+
+/// Instructions `continue` and `until` run before giving control back, in
+/// case the program never reaches a breakpoint.
+pub const RUN_LIMIT: u64 = 10_000_000;
+
+/// Where `Debugger::run` should stop besides breakpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopAt {
+    Address(usize),
+
+    Depth(usize), //the call stack is no deeper than this, e.g. after a return
+}
+
+/// Why `Debugger::run` stopped.
+#[derive(Debug)]
+pub enum Stop {
+    Done, //executed as many instructions as asked
+
+    Reached, //the StopAt condition
+
+    Breakpoint,
+
+    Exited,
+
+    Fault(CpuError),
+}
+
+/// What the caller should do after a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Output(String),
+
+    Quit,
+}
+
+pub struct Debugger {
+    pub breakpoints: BTreeSet<usize>,
+
+    pub timing: Timing, //how instruction counts map onto timer ticks and vblanks
+
+    pub cycles: u64, //instructions executed since the debugger started
+
+    resuming: bool,
+}
+
+impl Debugger {
+    pub fn new(timing: Timing) -> Debugger {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            timing,
+            cycles: 0,
+            resuming: false,
+        }
+    }
+
+    /// Runs one command line against `cpu`. Errors are messages about the
+    /// command itself; faults in the program are reported as output.
+    pub fn execute(&mut self, cpu: &mut CPU, line: &str) -> Result<Outcome, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(Outcome::Output(String::new())),
+        };
+        let args: Vec<&str> = words.collect();
+
+        let output = match command {
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(n) => parse_number(n)? as u64,
+                    None => 1,
+                };
+                self.run_command(cpu, Some(count), None)
+            }
+            "c" | "continue" => self.run_command(cpu, None, None),
+            "u" | "until" => {
+                let target = parse_number(arg(&args, 0, "address")?)?;
+                self.run_command(cpu, None, Some(StopAt::Address(target)))
+            }
+            "b" | "break" => match args.first() {
+                Some(addr) => {
+                    let addr = parse_number(addr)?;
+                    self.breakpoints.insert(addr);
+                    format!("breakpoint at {:04X}", addr)
+                }
+                None if self.breakpoints.is_empty() => "no breakpoints".to_string(),
+                None => self
+                    .breakpoints
+                    .iter()
+                    .map(|addr| format!("{:04X}", addr))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            },
+            "d" | "delete" => {
+                let addr = parse_number(arg(&args, 0, "address")?)?;
+                if !self.breakpoints.remove(&addr) {
+                    return Err(format!("no breakpoint at {:04X}", addr));
+                }
+                format!("deleted breakpoint at {:04X}", addr)
+            }
+            "r" | "regs" => registers(cpu),
+            "i" | "inst" => instruction_at(cpu, cpu.pc),
+            "x" | "dump" => {
+                let addr = parse_number(arg(&args, 0, "address")?)?;
+                let len = match args.get(1) {
+                    Some(len) => parse_number(len)?,
+                    None => 64,
+                };
+                dump(cpu.mem.get_ref(), addr, len)?
+            }
+            "w" | "write" => {
+                let addr = parse_number(arg(&args, 0, "address")?)?;
+                let bytes = args[1..]
+                    .iter()
+                    .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("bad byte {}", b)))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                let mem = cpu.mem.get_mut();
+                let end = match addr.checked_add(bytes.len()) {
+                    Some(end) if !bytes.is_empty() && end <= mem.len() => end,
+                    _ => return Err("write needs bytes that fit in memory".to_string()),
+                };
+                mem[addr..end].copy_from_slice(&bytes);
+                format!("wrote {} bytes at {:04X}", bytes.len(), addr)
+            }
+            "h" | "help" => HELP.to_string(),
+            "q" | "quit" => return Ok(Outcome::Quit),
+            _ => return Err(format!("unknown command {}, try help", command)),
+        };
+        Ok(Outcome::Output(output))
+    }
+
+    /// Makes the next `run` execute the current instruction even if it has a
+    /// breakpoint, as when continuing from one.
+    pub fn resume(&mut self) {
+        self.resuming = true;
+    }
+
+    /// Executes up to `count` instructions, stopping early at a breakpoint,
+    /// `until`, program exit or a fault. Timers and vblank advance as they
+    /// would at `timing`.
+    pub fn run(&mut self, cpu: &mut CPU, count: u64, until: Option<StopAt>) -> Stop {
+        for _ in 0..count {
+            if cpu.halted {
+                return Stop::Exited;
+            }
+            if !self.resuming {
+                let reached = match until {
+                    Some(StopAt::Address(addr)) => cpu.pc == addr,
+                    Some(StopAt::Depth(depth)) => cpu.stack.len() <= depth,
+                    None => false,
+                };
+                if reached {
+                    return Stop::Reached;
+                }
+                if self.breakpoints.contains(&cpu.pc) {
+                    return Stop::Breakpoint;
+                }
+            }
+            self.resuming = false;
+
+            if let Err(e) = cpu.emulate_cycle() {
+                return Stop::Fault(e);
+            }
+            self.tick(cpu);
+        }
+        Stop::Done
+    }
+
+    /// `run` from a user command, described for the prompt. A `count` of None
+    /// runs until something stops it, giving up after `RUN_LIMIT`.
+    fn run_command(&mut self, cpu: &mut CPU, count: Option<u64>, until: Option<StopAt>) -> String {
+        self.resume();
+        let stop = self.run(cpu, count.unwrap_or(RUN_LIMIT), until);
+        let at = instruction_at(cpu, cpu.pc);
+        match stop {
+            Stop::Done if count.is_none() => format!("stopped after {} instructions", RUN_LIMIT),
+            Stop::Done | Stop::Reached => at,
+            Stop::Breakpoint => format!("breakpoint\n{}", at),
+            Stop::Exited => "program exited".to_string(),
+            Stop::Fault(e) => format!("fault: {}\n{}", e, at),
+        }
+    }
+
+    /// Advances timers and vblank for one more instruction.
+    fn tick(&mut self, cpu: &mut CPU) {
+        let clock = self.timing.clock_hz.max(1) as u64;
+        let before = self.cycles;
+        self.cycles += 1;
+
+        let crossed = |rate: u32| self.cycles * rate as u64 / clock > before * rate as u64 / clock;
+        if crossed(self.timing.timer_hz) {
+            cpu.tick_timers();
+        }
+        if crossed(FRAME_RATE) {
+            cpu.end_frame();
+        }
+    }
+}
+
+fn arg<'a>(args: &[&'a str], n: usize, what: &str) -> Result<&'a str, String> {
+    args.get(n)
+        .cloned()
+        .ok_or_else(|| format!("missing {}", what))
+}
+
+/// Parses a hex number, with or without a 0x prefix.
+fn parse_number(s: &str) -> Result<usize, String> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    usize::from_str_radix(digits, 16).map_err(|_| format!("bad number {}", s))
+}
+
+fn registers(cpu: &CPU) -> String {
+    let mut out = String::new();
+    for (n, v) in cpu.registers.iter().enumerate() {
+        let sep = if n % 8 == 7 { "\n" } else { " " };
+        write!(out, "V{:X}={:02X}{}", n, v, sep).unwrap();
+    }
+    writeln!(out, "PC={:04X} I={:04X}", cpu.pc, cpu.index_reg).unwrap();
+    writeln!(out, "DT={:02X} ST={:02X}", cpu.delay_timer, cpu.sound_timer).unwrap();
+    let stack: Vec<String> = cpu.stack.iter().map(|a| format!("{:04X}", a)).collect();
+    write!(out, "stack=[{}]", stack.join(" ")).unwrap();
+    out
+}
+
+fn instruction_at(cpu: &CPU, pc: usize) -> String {
+    let mem = cpu.mem.get_ref();
+    if pc >= mem.len() {
+        return format!("{:04X}: out of memory", pc);
+    }
+    let line = Line::decode(mem, pc);
+    let bytes: String = line.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("{:04X}: {} {}", pc, bytes, line.mnemonic(&Labels::new()))
+}
+
+fn dump(mem: &[u8], addr: usize, len: usize) -> Result<String, String> {
+    if addr >= mem.len() {
+        return Err(format!("{:04X} is out of memory", addr));
+    }
+    let end = addr.saturating_add(len).min(mem.len());
+    let lines: Vec<String> = mem[addr..end]
+        .chunks(16)
+        .enumerate()
+        .map(|(n, row)| {
+            let bytes: Vec<String> = row.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("{:04X}: {}", addr + n * 16, bytes.join(" "))
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,164 @@
+//! Disassembly of ROM images into annotated listings.
+//!
+//! The ROM is walked linearly from `PROGRAM_START`, one instruction per word
+//! (two for F000 NNNN). Words that do not decode are shown as `DW` data, and
+//! every address some JP or CALL targets gets a label.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use cpu::PROGRAM_START;
+use instructions::Instructions;
+
+/// One instruction or data word of a listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: usize,
+
+    pub bytes: Vec<u8>, //everything the line covers, operand words included
+
+    pub instruction: Option<Instructions>, //None for data
+}
+
+impl Line {
+    /// Decodes the line at `address` of `mem`, which is indexed by address.
+    pub fn decode(mem: &[u8], address: usize) -> Line {
+        let word = match mem.get(address..address + 2) {
+            Some(word) => word,
+            None => {
+                return Line {
+                    address,
+                    bytes: mem.get(address).into_iter().cloned().collect(),
+                    instruction: None,
+                }
+            }
+        };
+        let opcode = (word[0] as u16) << 8 | word[1] as u16;
+        let instruction = Instructions::decode(opcode, address).ok();
+
+        //F000 is only an instruction if its operand is there too
+        let len = match instruction {
+            Some(Instructions::LongIndex) if address + 4 > mem.len() => {
+                return Line {
+                    address,
+                    bytes: word.to_vec(),
+                    instruction: None,
+                }
+            }
+            Some(Instructions::LongIndex) => 4,
+            _ => 2,
+        };
+        Line {
+            address,
+            bytes: mem[address..address + len].to_vec(),
+            instruction,
+        }
+    }
+
+    /// The address a jump or call on this line goes to.
+    pub fn target(&self) -> Option<usize> {
+        match self.instruction {
+            Some(Instructions::JumpToAddress(addr))
+            | Some(Instructions::CallSub(addr))
+            | Some(Instructions::JumpToValue { value: addr }) => Some(addr as usize),
+            _ => None,
+        }
+    }
+
+    /// The assembler text for this line, naming targets from `labels`.
+    pub fn mnemonic(&self, labels: &Labels) -> String {
+        let label = |addr: u16| labels.get(&(addr as usize)).cloned();
+        match self.instruction {
+            Some(Instructions::LongIndex) => {
+                format!(
+                    "LD I, LONG 0x{:04X}",
+                    (self.bytes[2] as u16) << 8 | self.bytes[3] as u16
+                )
+            }
+            Some(Instructions::JumpToAddress(addr)) if label(addr).is_some() => {
+                format!("JP {}", label(addr).unwrap())
+            }
+            Some(Instructions::CallSub(addr)) if label(addr).is_some() => {
+                format!("CALL {}", label(addr).unwrap())
+            }
+            Some(Instructions::JumpToValue { value }) if label(value).is_some() => {
+                format!("JP V0, {}", label(value).unwrap())
+            }
+            Some(instruction) => instruction.to_string(),
+            None if self.bytes.len() == 2 => {
+                format!("DW 0x{:02X}{:02X}", self.bytes[0], self.bytes[1])
+            }
+            None => format!("DB 0x{:02X}", self.bytes.first().cloned().unwrap_or(0)),
+        }
+    }
+}
+
+/// Label names by address.
+pub type Labels = BTreeMap<usize, String>;
+
+/// A disassembled ROM. Its `Display` is the listing text.
+pub struct Listing {
+    pub lines: Vec<Line>,
+
+    pub labels: Labels,
+}
+
+/// Disassembles a ROM image loaded at `PROGRAM_START`.
+pub fn disassemble(rom: &[u8]) -> Listing {
+    let mut mem = vec![0_u8; PROGRAM_START];
+    mem.extend_from_slice(rom);
+
+    let mut lines = vec![];
+    let mut address = PROGRAM_START;
+    while address < mem.len() {
+        let line = Line::decode(&mem, address);
+        address += line.bytes.len();
+        lines.push(line);
+    }
+
+    //only addresses that start a line can carry a label
+    let mut labels = Labels::new();
+    for line in &lines {
+        let target = match line.target() {
+            Some(target) => target,
+            None => continue,
+        };
+        if lines.binary_search_by_key(&target, |l| l.address).is_err() {
+            continue;
+        }
+        let name = match line.instruction {
+            Some(Instructions::CallSub(_)) => format!("sub_{:03X}", target),
+            _ => format!("label_{:03X}", target),
+        };
+        //a call makes the better name when an address is both
+        let entry = labels.entry(target).or_insert_with(|| name.clone());
+        if name.starts_with("sub_") {
+            *entry = name;
+        }
+    }
+
+    Listing { lines, labels }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = self.labels.get(&line.address) {
+                writeln!(f, "{}:", label)?;
+            }
+            let bytes: Vec<String> = line
+                .bytes
+                .chunks(2)
+                .map(|w| w.iter().map(|b| format!("{:02X}", b)).collect())
+                .collect();
+            writeln!(
+                f,
+                "    {:03X}: {:<9}  {}",
+                line.address,
+                bytes.join(" "),
+                line.mnemonic(&self.labels)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+//! Frontend backends.
+//!
+//! A frontend is a `Display` that presents the framebuffer, an `Input` that
+//! maintains the 16-key keypad and an `Audio` sink for the beeper. `run` drives
+//! a `CPU` against any combination of them, so SDL, a terminal or a headless
+//! harness all share the same main loop.
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use audio::Beeper;
+use cpu::{CpuError, CPU};
+use gpu::GPU;
+use movie::{Movie, Playback};
+use rewind::RewindBuffer;
+use savestate::{SaveSlots, StateError};
+
+/// Requests from the user that are not keypad input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    ToggleMute,
+    SaveState(u8), //slot number
+    LoadState(u8),
+    Rewind(bool), //step backwards through recent frames while true
+}
+
+/// Something `Runner` did that the user may want to hear about.
+#[derive(Debug)]
+pub enum Event {
+    SkippedFault(CpuError), //the ErrorPolicy skipped it and the run carried on
+
+    StateSaved(u8), //slot number
+
+    StateLoaded(u8),
+
+    SaveFailed(u8, StateError),
+
+    LoadFailed(u8, StateError),
+
+    LoadRefused, //a load or rewind was asked for during a movie
+
+    MovieDesync(usize), //first frame whose screen differed from the recording
+
+    MovieEnded(usize), //frames played
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::SkippedFault(ref e) => write!(f, "skipped fault: {}", e),
+            Event::StateSaved(slot) => write!(f, "saved state {}", slot),
+            Event::StateLoaded(slot) => write!(f, "loaded state {}", slot),
+            Event::SaveFailed(slot, ref e) => write!(f, "save state {}: {}", slot, e),
+            Event::LoadFailed(slot, ref e) => write!(f, "load state {}: {}", slot, e),
+            Event::LoadRefused => write!(f, "cannot load or rewind during a movie"),
+            Event::MovieDesync(frame) => write!(f, "movie desync at frame {}", frame),
+            Event::MovieEnded(frames) => write!(f, "movie ended after {} frames", frames),
+        }
+    }
+}
+
+/// The conventional mapping of the hex keypad onto the left of a QWERTY
+/// keyboard:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  <-  q w e r
+/// 7 8 9 E      a s d f
+/// A 0 B F      z x c v
+/// ```
+pub fn qwerty_keypad(c: char) -> Option<usize> {
+    match c.to_ascii_lowercase() {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xC),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xD),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xE),
+        'z' => Some(0xA),
+        'x' => Some(0x0),
+        'c' => Some(0xB),
+        'v' => Some(0xF),
+        _ => None,
+    }
+}
+
+pub trait Display {
+    /// Draws the current contents of the framebuffer.
+    fn present(&mut self, gpu: &GPU);
+}
+
+pub trait Input {
+    /// Updates `keys` (1 = pressed) from pending input and returns any commands.
+    fn poll(&mut self, keys: &mut [u8; 16]) -> Vec<Command>;
+}
+
+pub trait Audio {
+    /// Called once per displayed frame with what the beeper should play.
+    fn update(&mut self, beeper: &Beeper);
+
+    fn toggle_mute(&mut self) {}
+}
+
+/// A display that keeps a copy of every presented frame.
+#[derive(Default)]
+pub struct FrameRecorder {
+    pub frames: Vec<Vec<u8>>,
+}
+
+impl Display for FrameRecorder {
+    fn present(&mut self, gpu: &GPU) {
+        self.frames.push(gpu.gfx.to_vec());
+    }
+}
+
+/// A display that draws nothing.
+pub struct NullDisplay;
+
+impl Display for NullDisplay {
+    fn present(&mut self, _gpu: &GPU) {}
+}
+
+/// An input that never presses a key.
+pub struct NullInput;
+
+impl Input for NullInput {
+    fn poll(&mut self, _keys: &mut [u8; 16]) -> Vec<Command> {
+        vec![]
+    }
+}
+
+/// An audio sink that stays silent.
+pub struct NullAudio;
+
+impl Audio for NullAudio {
+    fn update(&mut self, _beeper: &Beeper) {}
+}
+
+/// How fast the emulated machine runs. Instructions and timers are clocked
+/// independently of each other and of the display refresh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub clock_hz: u32, //instructions per second
+
+    pub timer_hz: u32, //delay/sound timer ticks per second
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            clock_hz: 500,
+            timer_hz: 60,
+        }
+    }
+}
+
+/// Display refreshes per second.
+pub const FRAME_RATE: u32 = 60;
+
+/// Drives a `CPU` in real time against a frontend.
+pub struct Runner {
+    pub timing: Timing,
+
+    pub save_slots: Option<SaveSlots>, //where SaveState/LoadState commands go, ignored if unset
+
+    pub rewind: Option<RewindBuffer>, //frame history for Rewind commands, ignored if unset
+
+    pub recording: Option<Movie>, //appended to every frame
+
+    pub playback: Option<Playback>, //replaces live keypad input until it runs out
+
+    pub on_event: Option<Box<dyn FnMut(Event)>>, //told about each Event, ignored if unset
+
+    rewinding: bool,
+}
+
+impl Runner {
+    pub fn new(timing: Timing) -> Runner {
+        Runner {
+            timing,
+            save_slots: None,
+            rewind: None,
+            recording: None,
+            playback: None,
+            on_event: None,
+            rewinding: false,
+        }
+    }
+
+    /// Runs `cpu` until the input asks to quit, the program exits (00FD), a
+    /// movie being played ends or it hits a fault its `ErrorPolicy` halts on.
+    /// Skipped faults, save state results and movie progress are reported to
+    /// `on_event`.
+    pub fn run<D, I, A>(
+        &mut self,
+        cpu: &mut CPU,
+        display: &mut D,
+        input: &mut I,
+        audio: &mut A,
+    ) -> Result<(), CpuError>
+    where
+        D: Display + ?Sized,
+        I: Input + ?Sized,
+        A: Audio + ?Sized,
+    {
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut next_frame = Instant::now();
+
+        loop {
+            for command in input.poll(&mut cpu.keys) {
+                match command {
+                    Command::Quit => return Ok(()),
+                    Command::ToggleMute => audio.toggle_mute(),
+                    Command::SaveState(slot) => self.save_state(cpu, slot),
+                    //a movie only replays if nothing but the keypad changes the machine
+                    Command::LoadState(_) | Command::Rewind(true) if self.movie_active() => {
+                        self.report(Event::LoadRefused)
+                    }
+                    Command::LoadState(slot) => self.load_state(cpu, slot),
+                    Command::Rewind(on) => self.rewinding = on,
+                }
+            }
+            if cpu.halted {
+                return Ok(());
+            }
+            let ended = match self.playback {
+                Some(ref playback) if playback.finished() => Some(playback.frame),
+                Some(ref playback) => {
+                    playback.feed(&mut cpu.keys);
+                    None
+                }
+                None => None,
+            };
+            if let Some(frames) = ended {
+                self.report(Event::MovieEnded(frames));
+                return Ok(());
+            }
+
+            match self.rewind {
+                Some(ref mut history) if self.rewinding => {
+                    history.step_back(cpu);
+                    cpu.end_frame();
+                }
+                _ => {
+                    for fault in cpu.run_frame(self.timing)? {
+                        self.report(Event::SkippedFault(fault));
+                    }
+                    if let Some(ref mut history) = self.rewind {
+                        history.push(cpu);
+                    }
+                    if let Some(ref mut movie) = self.recording {
+                        movie.record(&cpu.keys, &cpu.gpu);
+                    }
+                    let desync = self.playback.as_mut().and_then(|playback| {
+                        if playback.verify(&cpu.gpu) {
+                            None
+                        } else {
+                            Some(playback.frame - 1)
+                        }
+                    });
+                    if let Some(frame) = desync {
+                        self.report(Event::MovieDesync(frame));
+                    }
+                }
+            }
+
+            display.present(&cpu.gpu);
+            audio.update(&Beeper::from_cpu(cpu));
+
+            next_frame += frame;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                //too far behind to catch up, e.g. after the process was suspended
+                next_frame = now;
+            }
+        }
+    }
+
+    fn report(&mut self, event: Event) {
+        if let Some(ref mut on_event) = self.on_event {
+            on_event(event);
+        }
+    }
+
+    fn movie_active(&self) -> bool {
+        self.recording.is_some() || self.playback.is_some()
+    }
+
+    fn save_state(&mut self, cpu: &CPU, slot: u8) {
+        let event = match self.save_slots {
+            Some(ref slots) => match slots.save(cpu, slot) {
+                Ok(()) => Event::StateSaved(slot),
+                Err(e) => Event::SaveFailed(slot, e),
+            },
+            None => return,
+        };
+        self.report(event);
+    }
+
+    fn load_state(&mut self, cpu: &mut CPU, slot: u8) {
+        let event = match self.save_slots {
+            Some(ref slots) => match slots.load(cpu, slot) {
+                Ok(()) => Event::StateLoaded(slot),
+                Err(e) => Event::LoadFailed(slot, e),
+            },
+            None => return,
+        };
+        self.report(event);
+    }
+}
+
+/// Runs `cpu` in real time with `Runner`, without save slots.
+pub fn run<D, I, A>(
+    cpu: &mut CPU,
+    timing: Timing,
+    display: &mut D,
+    input: &mut I,
+    audio: &mut A,
+) -> Result<(), CpuError>
+where
+    D: Display + ?Sized,
+    I: Input + ?Sized,
+    A: Audio + ?Sized,
+{
+    Runner::new(timing).run(cpu, display, input, audio)
+}
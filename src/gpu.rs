@@ -1,107 +1,147 @@
-extern crate sdl2;
+//low resolution (CHIP-8)
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
 
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::video::WindowBuildError;
-use sdl2::IntegerOrSdlError;
+//high resolution (SUPER-CHIP)
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
 
-use std::time::Duration;
+pub const PLANES: usize = 2; //XO-CHIP bitplanes
 
-pub const DISPLAY_FREQ: Duration = Duration::from_millis(16);
+pub struct GPU {
+    pub gfx: Vec<u8>, //width() * height() pixels, bit n set = lit on plane n + 1, 0 = off
+
+    pub hires: bool, //128x64 instead of 64x32
 
-#[derive(Debug)]
-pub enum GpuError {
-    WindowError { inner: WindowBuildError },
-    SdlError { inner: IntegerOrSdlError },
-    CommonError(String),
+    pub planes: u8, //XO-CHIP plane mask that drawing, clearing and scrolling affect
 }
 
-impl From<String> for GpuError {
-    fn from(error_msg: String) -> Self {
-        GpuError::CommonError(error_msg)
+impl GPU {
+    pub fn new() -> GPU {
+        GPU {
+            gfx: vec![0_u8; WIDTH * HEIGHT],
+            hires: false,
+            planes: 1,
+        }
     }
-}
 
-impl From<WindowBuildError> for GpuError {
-    fn from(window_error: WindowBuildError) -> Self {
-        GpuError::WindowError {
-            inner: window_error,
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            WIDTH
         }
     }
-}
 
-impl From<IntegerOrSdlError> for GpuError {
-    fn from(sdl_error: IntegerOrSdlError) -> Self {
-        GpuError::SdlError { inner: sdl_error }
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            HEIGHT
+        }
     }
-}
 
-struct GConfig {
-    background_color: Color,
+    /// Switches resolution, clearing the screen.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.gfx = vec![0_u8; self.width() * self.height()];
+    }
 
-    block_color: Color,
-}
+    /// Number of planes selected, i.e. how many copies of a sprite DXYN reads.
+    pub fn selected_planes(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
 
-pub struct GPU {
-    pub gfx: [u8; 64 * 32], //2048 pixels
+    /// Clears the selected planes.
+    pub fn clear(&mut self) {
+        let keep = !self.planes;
+        self.gfx.iter_mut().for_each(|p| *p &= keep);
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.gfx[y * self.width() + x]
+    }
 
-    pub ctx: sdl2::Sdl, //sdl context
+    /// XORs a sprite onto the selected planes with its top-left corner at (`x`,
+    /// `y`), returning whether any lit pixel was turned off. Each row of
+    /// `sprite` is one byte, or two bytes when `wide` (16x16 SUPER-CHIP
+    /// sprites); with several planes selected `sprite` holds one such image per
+    /// plane, lowest plane first. Pixels past the edge wrap around when `wrap`
+    /// is set and are clipped otherwise.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], wide: bool, wrap: bool) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let (x, y) = (x % width, y % height);
+        let row_bytes = if wide { 2 } else { 1 };
+        let cols = row_bytes * 8;
+        let planes = self.selected_planes().max(1);
+        let plane_len = sprite.len() / planes;
+        let mut collision = false;
+
+        let mask = self.planes;
+        let selected = (0..PLANES).map(|n| 1_u8 << n).filter(|bit| mask & bit != 0);
+        for (bit, image) in selected.zip(sprite.chunks(plane_len.max(1))) {
+            for (yline, row) in image.chunks(row_bytes).enumerate() {
+                let bits = row.iter().fold(0_u16, |acc, b| (acc << 8) | *b as u16);
+                for xline in 0..cols {
+                    if bits & (1 << (cols - 1 - xline)) == 0 {
+                        continue;
+                    }
+                    let (px, py) = (x + xline, y + yline);
+                    if !wrap && (px >= width || py >= height) {
+                        continue;
+                    }
+                    let i = px % width + (py % height) * width;
+                    if self.gfx[i] & bit != 0 {
+                        collision = true;
+                    }
+                    self.gfx[i] ^= bit;
+                }
+            }
+        }
 
-    pub canvas: sdl2::render::Canvas<sdl2::video::Window>, //sdl canvas
+        collision
+    }
 
-    config: GConfig, //color configs
-}
+    /// Moves the selected planes by `dx` columns and `dy` rows, filling the
+    /// vacated area with unlit pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let mask = self.planes;
+        let old = self.gfx.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    old[(sy * width + sx) as usize] & mask
+                } else {
+                    0
+                };
+                let p = &mut self.gfx[(y * width + x) as usize];
+                *p = (*p & !mask) | moved;
+            }
+        }
+    }
 
-impl GPU {
-    pub fn new() -> Result<GPU, GpuError> {
-        let sdl_context = sdl2::init().map_err(|e| GpuError::from(e))?;
-        let video_subsystem = sdl_context.video().map_err(|e| GpuError::from(e))?;
-        let window = video_subsystem
-            .window("CHIP-8", 800, 400)
-            .position_centered()
-            .opengl()
-            .build()
-            .map_err(|e| GpuError::from(e))?;
-
-        let canvas = window.into_canvas().build().map_err(|e| GpuError::from(e))?;
-
-        Ok(GPU {
-            gfx: [0_u8; 64 * 32],
-            ctx: sdl_context,
-            canvas,
-            config: GConfig {
-                background_color: Color::RGB(0, 0, 0),
-                block_color: Color::RGB(255, 255, 255),
-            },
-        })
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.scroll(0, rows as isize);
     }
 
-    pub fn show(&mut self) {
-        self.canvas.set_draw_color(self.config.background_color);
-        self.canvas.clear();
-        self.canvas.present();
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.scroll(0, -(rows as isize));
     }
 
-    pub fn refresh(&mut self) {
-        self.reset();
-        self.canvas.set_draw_color(self.config.block_color);
-        for y in 0..32 {
-            for x in 0..64 {
-                if self.gfx[(y * 32) + x] == 1 {
-                    let _result = self.canvas.fill_rect(Rect::new(
-                        (x as u8) as i32 * 10,
-                        (y as u8) as i32 * 10,
-                        10,
-                        10,
-                    ));
-                }
-            }
-        }
-        self.canvas.present();
+    pub fn scroll_right(&mut self, cols: usize) {
+        self.scroll(cols as isize, 0);
     }
 
-    fn reset(&mut self) {
-        self.canvas.set_draw_color(self.config.background_color);
-        self.canvas.clear();
+    pub fn scroll_left(&mut self, cols: usize) {
+        self.scroll(-(cols as isize), 0);
+    }
+}
+
+impl Default for GPU {
+    fn default() -> GPU {
+        GPU::new()
     }
 }
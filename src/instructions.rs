@@ -1,10 +1,31 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
 use bitrange::*;
 
+/// Every instruction of CHIP-8, SUPER-CHIP and XO-CHIP. The comments name the
+/// extension an instruction comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instructions {
+    ScrollDown(u8), //00CN, SUPER-CHIP
+
+    ScrollUp(u8), //00DN, XO-CHIP
+
     ClearScreen, //00E0
 
     Return, //00EE
 
+    ScrollRight, //00FB, SUPER-CHIP, 4 pixels
+
+    ScrollLeft, //00FC, SUPER-CHIP, 4 pixels
+
+    Exit, //00FD, SUPER-CHIP
+
+    LowRes, //00FE, SUPER-CHIP, 64x32
+
+    HighRes, //00FF, SUPER-CHIP, 128x64
+
     JumpToAddress(u16), //1NNN
 
     CallSub(u16), //2NNN
@@ -15,6 +36,10 @@ pub enum Instructions {
 
     SkipIfRegEqual { x: u8, y: u8 }, //5XY0
 
+    SaveRange { x: u8, y: u8 }, //5XY2, XO-CHIP, store VX..VY at I, I unchanged
+
+    LoadRange { x: u8, y: u8 }, //5XY3, XO-CHIP, load VX..VY from I, I unchanged
+
     SetValueToReg { x: u8, value: u8 }, //6XNN
 
     AddValueToReg { x: u8, value: u8 }, //7XNN (Don't change carry flag)
@@ -31,26 +56,32 @@ pub enum Instructions {
 
     AssignSubValue { x: u8, y: u8 }, //8XY5, Vx -= Vy, VF is set to 0 when there's a borrow, and 1 when there isn't.
 
-    ShiftRight { x: u8 }, //8XY6, Stores the least significant bit of VX in VF and then shifts VX to the right by 1
+    ShiftRight { x: u8, y: u8 }, //8XY6, Stores the least significant bit of VX (or VY, see Quirks) in VF and then shifts it to the right by 1 into VX
 
     AssignMinusValue { x: u8, y: u8 }, //8XY7, Vx=Vy-Vx, VF is set to 0 when there's a borrow, and 1 when there isn't.
 
-    ShiftLeft { x: u8 }, //8XYE, Stores the most significant bit of VX in VF and then shifts VX to the left by 1
+    ShiftLeft { x: u8, y: u8 }, //8XYE, Stores the most significant bit of VX (or VY, see Quirks) in VF and then shifts it to the left by 1 into VX
 
     SkipIfRegNotEqual { x: u8, y: u8 }, //9XY0, if(Vx!=Vy)
 
     SetMem { value: u16 }, //ANNN
 
-    JumpToValue { value: u16 }, //BNNN, PC=V0+NNN
+    JumpToValue { value: u16 }, //BNNN, PC=V0+NNN (or VX+XNN, see Quirks)
 
     RandomAnd { x: u8, value: u8 }, //CXNN, Vx=rand()&NN
 
-    Display { x: u8, y: u8, value: u8 }, //DXYN, draw(Vx,Vy,8,N)
+    Display { x: u8, y: u8, value: u8 }, //DXYN, draw(Vx,Vy,8,N); DXY0 draws a 16x16 sprite (SUPER-CHIP)
 
     PressedKey { x: u8 }, //EX9E, if(key()==Vx), skip
 
     NotPressedKey { x: u8 }, //EXA1, if(key()!=Vx), skip
 
+    LongIndex, //F000 NNNN, XO-CHIP, I=NNNN, the address is the next word
+
+    SelectPlane(u8), //FN01, XO-CHIP, plane mask for drawing
+
+    AudioPattern, //F002, XO-CHIP, load the 16-byte audio pattern from I
+
     SetValueToDelayTimer { x: u8 }, //FX07,
 
     WaitForKey { x: u8 }, //FX0A
@@ -63,20 +94,257 @@ pub enum Instructions {
 
     SetIFromSprite { x: u8 }, //FX29
 
+    SetIFromBigSprite { x: u8 }, //FX30, SUPER-CHIP, 10-byte digit
+
+    SetPitch { x: u8 }, //FX3A, XO-CHIP, audio pattern playback rate
+
     BCD { x: u8 }, //FX33
 
     RegDump { x: u8 }, //FX55
 
     RegLoad { x: u8 }, //FX65
+
+    SaveFlags { x: u8 }, //FX75, SUPER-CHIP, V0..VX to RPL flags
+
+    LoadFlags { x: u8 }, //FX85, SUPER-CHIP, RPL flags to V0..VX
+}
+
+/// A word that is not a valid instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+
+    pub address: Option<usize>, //where the word was read from, if known
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.address {
+            Some(address) => write!(f, "invalid opcode {:04X} at {:03X}", self.opcode, address),
+            None => write!(f, "invalid opcode {:04X}", self.opcode),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// An operand too wide for its field of the opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    Address(u16), //more than 12 bits
+
+    Register(u8), //more than VF
+
+    Nibble(u8), //more than 4 bits
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::Address(addr) => write!(f, "address 0x{:X} does not fit in 12 bits", addr),
+            EncodeError::Register(x) => write!(f, "there is no register V{}", x),
+            EncodeError::Nibble(n) => write!(f, "{} does not fit in 4 bits", n),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+impl Instructions {
+    /// Decodes the word read from `address`.
+    ///
+    /// The opcodes of the extensions do not collide, so decoding does not
+    /// depend on the `Platform`: a COSMAC VIP program can still run 00FF or
+    /// F000 NNNN. Only the quirks and the memory size change between platforms.
+    pub fn decode(opcode: u16, address: usize) -> Result<Instructions, DecodeError> {
+        Instructions::try_from(opcode).map_err(|e| DecodeError {
+            address: Some(address),
+            ..e
+        })
+    }
+
+    /// The word this instruction decodes from. `LongIndex` is only the F000
+    /// word; its address follows separately.
+    pub fn encode(&self) -> Result<u16, EncodeError> {
+        use self::Instructions::*;
+
+        let op = |high: u16, x: u8, y: u8, n: u8| -> Result<u16, EncodeError> {
+            Ok(high << 12
+                | (register(x)? as u16) << 8
+                | (register(y)? as u16) << 4
+                | nibble(n)? as u16)
+        };
+        let xnn = |high: u16, x: u8, value: u8| {
+            Ok(high << 12 | (register(x)? as u16) << 8 | value as u16)
+        };
+        let fx = |x: u8, low: u16| Ok(0xF000 | (register(x)? as u16) << 8 | low);
+
+        match *self {
+            ScrollDown(n) => Ok(0x00C0 | nibble(n)? as u16),
+            ScrollUp(n) => Ok(0x00D0 | nibble(n)? as u16),
+            ClearScreen => Ok(0x00E0),
+            Return => Ok(0x00EE),
+            ScrollRight => Ok(0x00FB),
+            ScrollLeft => Ok(0x00FC),
+            Exit => Ok(0x00FD),
+            LowRes => Ok(0x00FE),
+            HighRes => Ok(0x00FF),
+            JumpToAddress(addr) => Ok(0x1000 | address(addr)?),
+            CallSub(addr) => Ok(0x2000 | address(addr)?),
+            SkipIfEqual { x, value } => xnn(0x3, x, value),
+            SkipIfNotEqualValue { x, value } => xnn(0x4, x, value),
+            SkipIfRegEqual { x, y } => op(0x5, x, y, 0x0),
+            SaveRange { x, y } => op(0x5, x, y, 0x2),
+            LoadRange { x, y } => op(0x5, x, y, 0x3),
+            SetValueToReg { x, value } => xnn(0x6, x, value),
+            AddValueToReg { x, value } => xnn(0x7, x, value),
+            AssignValueToReg { x, y } => op(0x8, x, y, 0x0),
+            AssignOrValue { x, y } => op(0x8, x, y, 0x1),
+            AssignAndValue { x, y } => op(0x8, x, y, 0x2),
+            AssignXorValue { x, y } => op(0x8, x, y, 0x3),
+            AssignAddValue { x, y } => op(0x8, x, y, 0x4),
+            AssignSubValue { x, y } => op(0x8, x, y, 0x5),
+            ShiftRight { x, y } => op(0x8, x, y, 0x6),
+            AssignMinusValue { x, y } => op(0x8, x, y, 0x7),
+            ShiftLeft { x, y } => op(0x8, x, y, 0xE),
+            SkipIfRegNotEqual { x, y } => op(0x9, x, y, 0x0),
+            SetMem { value } => Ok(0xA000 | address(value)?),
+            JumpToValue { value } => Ok(0xB000 | address(value)?),
+            RandomAnd { x, value } => xnn(0xC, x, value),
+            Display { x, y, value } => op(0xD, x, y, value),
+            PressedKey { x } => xnn(0xE, x, 0x9E),
+            NotPressedKey { x } => xnn(0xE, x, 0xA1),
+            LongIndex => Ok(0xF000),
+            SelectPlane(n) => Ok(0xF001 | (nibble(n)? as u16) << 8),
+            AudioPattern => Ok(0xF002),
+            SetValueToDelayTimer { x } => fx(x, 0x07),
+            WaitForKey { x } => fx(x, 0x0A),
+            SetDelayTimerToReg { x } => fx(x, 0x15),
+            SetSoundTimerTOReg { x } => fx(x, 0x18),
+            SetIFromReg { x } => fx(x, 0x1E),
+            SetIFromSprite { x } => fx(x, 0x29),
+            SetIFromBigSprite { x } => fx(x, 0x30),
+            BCD { x } => fx(x, 0x33),
+            SetPitch { x } => fx(x, 0x3A),
+            RegDump { x } => fx(x, 0x55),
+            RegLoad { x } => fx(x, 0x65),
+            SaveFlags { x } => fx(x, 0x75),
+            LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+}
+
+impl TryFrom<Instructions> for u16 {
+    type Error = EncodeError;
+
+    fn try_from(instruction: Instructions) -> Result<u16, EncodeError> {
+        instruction.encode()
+    }
 }
 
-impl From<u16> for Instructions {
-    fn from(opcode: u16) -> Instructions {
-        match first(&opcode) {
-            0x0 => match last_two(&opcode) {
-                0xE0 => Instructions::ClearScreen,
-                0xEE => Instructions::Return,
-                _ => panic!("invalid opcode"),
+fn address(addr: u16) -> Result<u16, EncodeError> {
+    if addr > 0xFFF {
+        return Err(EncodeError::Address(addr));
+    }
+    Ok(addr)
+}
+
+fn register(x: u8) -> Result<u8, EncodeError> {
+    if x > 0xF {
+        return Err(EncodeError::Register(x));
+    }
+    Ok(x)
+}
+
+fn nibble(n: u8) -> Result<u8, EncodeError> {
+    if n > 0xF {
+        return Err(EncodeError::Nibble(n));
+    }
+    Ok(n)
+}
+
+/// Conventional CHIP-8 assembler mnemonics, e.g. `LD V3, 0x1F`. F000's
+/// address is the following word, so it prints as `LD I, LONG`.
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Instructions::*;
+
+        match *self {
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            ScrollUp(n) => write!(f, "SCU {}", n),
+            ClearScreen => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            LowRes => write!(f, "LOW"),
+            HighRes => write!(f, "HIGH"),
+            JumpToAddress(addr) => write!(f, "JP 0x{:03X}", addr),
+            CallSub(addr) => write!(f, "CALL 0x{:03X}", addr),
+            SkipIfEqual { x, value } => write!(f, "SE V{:X}, 0x{:02X}", x, value),
+            SkipIfNotEqualValue { x, value } => write!(f, "SNE V{:X}, 0x{:02X}", x, value),
+            SkipIfRegEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            SetValueToReg { x, value } => write!(f, "LD V{:X}, 0x{:02X}", x, value),
+            AddValueToReg { x, value } => write!(f, "ADD V{:X}, 0x{:02X}", x, value),
+            AssignValueToReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            AssignOrValue { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            AssignAndValue { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            AssignXorValue { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AssignAddValue { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            AssignSubValue { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            AssignMinusValue { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipIfRegNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            SetMem { value } => write!(f, "LD I, 0x{:03X}", value),
+            JumpToValue { value } => write!(f, "JP V0, 0x{:03X}", value),
+            RandomAnd { x, value } => write!(f, "RND V{:X}, 0x{:02X}", x, value),
+            Display { x, y, value } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, value),
+            PressedKey { x } => write!(f, "SKP V{:X}", x),
+            NotPressedKey { x } => write!(f, "SKNP V{:X}", x),
+            LongIndex => write!(f, "LD I, LONG"),
+            SelectPlane(n) => write!(f, "PLANE {}", n),
+            AudioPattern => write!(f, "AUDIO"),
+            SetValueToDelayTimer { x } => write!(f, "LD V{:X}, DT", x),
+            WaitForKey { x } => write!(f, "LD V{:X}, K", x),
+            SetDelayTimerToReg { x } => write!(f, "LD DT, V{:X}", x),
+            SetSoundTimerTOReg { x } => write!(f, "LD ST, V{:X}", x),
+            SetIFromReg { x } => write!(f, "ADD I, V{:X}", x),
+            SetIFromSprite { x } => write!(f, "LD F, V{:X}", x),
+            SetIFromBigSprite { x } => write!(f, "LD HF, V{:X}", x),
+            SetPitch { x } => write!(f, "PITCH V{:X}", x),
+            BCD { x } => write!(f, "LD B, V{:X}", x),
+            RegDump { x } => write!(f, "LD [I], V{:X}", x),
+            RegLoad { x } => write!(f, "LD V{:X}, [I]", x),
+            SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+impl TryFrom<u16> for Instructions {
+    type Error = DecodeError;
+
+    fn try_from(opcode: u16) -> Result<Instructions, DecodeError> {
+        let invalid = Err(DecodeError {
+            opcode,
+            address: None,
+        });
+
+        let instruction = match first(&opcode) {
+            0x0 => match opcode {
+                0x00C0..=0x00CF => Instructions::ScrollDown(last(&opcode)),
+                0x00D0..=0x00DF => Instructions::ScrollUp(last(&opcode)),
+                0x00E0 => Instructions::ClearScreen,
+                0x00EE => Instructions::Return,
+                0x00FB => Instructions::ScrollRight,
+                0x00FC => Instructions::ScrollLeft,
+                0x00FD => Instructions::Exit,
+                0x00FE => Instructions::LowRes,
+                0x00FF => Instructions::HighRes,
+                _ => return invalid,
             },
 
             0x1 => Instructions::JumpToAddress(last_three(&opcode)),
@@ -89,9 +357,20 @@ impl From<u16> for Instructions {
                 x: second(&opcode),
                 value: last_two(&opcode),
             },
-            0x5 => Instructions::SkipIfRegEqual {
-                x: second(&opcode),
-                y: third(&opcode),
+            0x5 => match last(&opcode) {
+                0x0 => Instructions::SkipIfRegEqual {
+                    x: second(&opcode),
+                    y: third(&opcode),
+                },
+                0x2 => Instructions::SaveRange {
+                    x: second(&opcode),
+                    y: third(&opcode),
+                },
+                0x3 => Instructions::LoadRange {
+                    x: second(&opcode),
+                    y: third(&opcode),
+                },
+                _ => return invalid,
             },
             0x6 => Instructions::SetValueToReg {
                 x: second(&opcode),
@@ -126,24 +405,30 @@ impl From<u16> for Instructions {
                     x: second(&opcode),
                     y: third(&opcode),
                 },
-                0x6 => Instructions::ShiftRight { x: second(&opcode)},
+                0x6 => Instructions::ShiftRight {
+                    x: second(&opcode),
+                    y: third(&opcode),
+                },
                 0x7 => Instructions::AssignMinusValue {
                     x: second(&opcode),
                     y: third(&opcode),
                 },
-                0xE => Instructions::ShiftLeft { x: second(&opcode)},
-
-                _ => panic!("invalid opcode"),
-            },
-            0x9 => Instructions::SkipIfRegNotEqual {
+                0xE => Instructions::ShiftLeft {
                     x: second(&opcode),
                     y: third(&opcode),
+                },
+
+                _ => return invalid,
+            },
+            0x9 if last(&opcode) == 0 => Instructions::SkipIfRegNotEqual {
+                x: second(&opcode),
+                y: third(&opcode),
             },
             0xA => Instructions::SetMem {
                 value: last_three(&opcode),
             },
             0xB => Instructions::JumpToValue {
-      value: last_three(&opcode),
+                value: last_three(&opcode),
             },
             0xC => Instructions::RandomAnd {
                 x: second(&opcode),
@@ -157,41 +442,38 @@ impl From<u16> for Instructions {
             0xE => match v(&opcode) {
                 0x9E => Instructions::PressedKey { x: second(&opcode) },
                 0xA1 => Instructions::NotPressedKey { x: second(&opcode) },
-                _ => panic!("invalid opcode"),
+                _ => return invalid,
             },
             0xF => match v(&opcode) {
+                0x00 if opcode == 0xF000 => Instructions::LongIndex,
+                0x01 => Instructions::SelectPlane(second(&opcode)),
+                0x02 if opcode == 0xF002 => Instructions::AudioPattern,
                 0x07 => Instructions::SetValueToDelayTimer { x: second(&opcode) },
                 0x0A => Instructions::WaitForKey { x: second(&opcode) },
                 0x15 => Instructions::SetDelayTimerToReg { x: second(&opcode) },
                 0x18 => Instructions::SetSoundTimerTOReg { x: second(&opcode) },
                 0x1E => Instructions::SetIFromReg { x: second(&opcode) },
                 0x29 => Instructions::SetIFromSprite { x: second(&opcode) },
+                0x30 => Instructions::SetIFromBigSprite { x: second(&opcode) },
                 0x33 => Instructions::BCD { x: second(&opcode) },
+                0x3A => Instructions::SetPitch { x: second(&opcode) },
                 0x55 => Instructions::RegDump { x: second(&opcode) },
                 0x65 => Instructions::RegLoad { x: second(&opcode) },
-                _ => panic!("invalid opcode"),
+                0x75 => Instructions::SaveFlags { x: second(&opcode) },
+                0x85 => Instructions::LoadFlags { x: second(&opcode) },
+                _ => return invalid,
             },
-            _ => panic!("invalid opcode"),
-        }
-    }
-}
+            _ => return invalid,
+        };
 
-fn x(opcode: &u16) -> u8 {
-    ((opcode & 0x0F00) >> 8) as u8
-}
-
-fn y(opcode: &u16) -> u8 {
-    ((opcode & 0x00F0) >> 4) as u8
+        Ok(instruction)
+    }
 }
 
 fn v(opcode: &u16) -> u8 {
     (opcode & 0x00FF) as u8
 }
 
-fn addr(opcode: &u16) -> u16 {
-    opcode & 0x0FFF
-}
-
 fn first(value: &u16) -> u8 {
     value.range_u8(12..15)
 }
@@ -0,0 +1,34 @@
+//! CHIP-8 interpreter core.
+//!
+//! The library has no windowing or audio dependencies: `CPU` executes
+//! instructions against a plain `GPU` framebuffer, and frontends (such as the
+//! SDL one in the `chip8-cli` crate's `chip8` binary) read that framebuffer
+//! and write `CPU::keys`.
+
+#![allow(clippy::upper_case_acronyms)]
+
+extern crate byteorder;
+extern crate rand;
+
+pub mod asm;
+pub mod audio;
+mod bitrange;
+pub mod coverage;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod frontend;
+pub mod gpu;
+pub mod instructions;
+pub mod movie;
+pub mod profile;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod savestate;
+pub mod trace;
+
+pub use cpu::CPU;
+pub use gpu::GPU;
+pub use instructions::Instructions;
+pub use quirks::{Platform, Quirks};
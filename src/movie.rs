@@ -0,0 +1,290 @@
+//! Input movies: the keypad state of every frame of a run, plus what is needed
+//! to replay it exactly.
+//!
+//! A movie starts at power-on and records the ROM hash, platform, timing, RNG
+//! seed and fault policy, then one entry per frame with the keys held and a checksum of the
+//! framebuffer after the frame ran. Playing it back feeds the keys into
+//! `CPU::keys` and compares checksums, so the first frame that diverges is
+//! reported.
+
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::{ErrorPolicy, FaultAction, CPU};
+use frontend::Timing;
+use gpu::GPU;
+use quirks::Platform;
+use rng::Rng;
+
+const MAGIC: &[u8; 4] = b"C8MV";
+
+/// The version `Movie::write` produces.
+pub const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum MovieError {
+    BadMagic,
+
+    UnsupportedVersion(u16),
+
+    Corrupt(&'static str),
+
+    Io(io::Error),
+}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> MovieError {
+        MovieError::Io(e)
+    }
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::BadMagic => write!(f, "not a movie file"),
+            MovieError::UnsupportedVersion(v) => {
+                write!(f, "movie version {} is newer than {}", v, VERSION)
+            }
+            MovieError::Corrupt(what) => write!(f, "corrupt movie: bad {}", what),
+            MovieError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for MovieError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            MovieError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub keys: u16, //bit n set = key n held
+
+    pub checksum: u32, //framebuffer after the frame ran
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+
+    pub platform: Platform,
+
+    pub timing: Timing,
+
+    pub seed: u32,
+
+    pub error_policy: ErrorPolicy, //a fault skipped while recording must not halt the replay
+
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    /// An empty movie of `rom` running on `cpu`, which must be freshly loaded.
+    pub fn new(rom: &[u8], cpu: &CPU, timing: Timing) -> Movie {
+        Movie {
+            rom_hash: fnv1a(rom),
+            platform: cpu.platform,
+            timing,
+            seed: cpu.rng.seed,
+            error_policy: cpu.error_policy,
+            frames: vec![],
+        }
+    }
+
+    /// Whether this movie was recorded with `rom`.
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        self.rom_hash == fnv1a(rom)
+    }
+
+    /// The generator the recording started with.
+    pub fn rng(&self) -> Rng {
+        Rng::new(self.seed)
+    }
+
+    /// Appends a frame: the keys held while it ran and the resulting screen.
+    pub fn record(&mut self, keys: &[u8; 16], gpu: &GPU) {
+        self.frames.push(MovieFrame {
+            keys: pack_keys(keys),
+            checksum: checksum(gpu),
+        });
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_u16::<BigEndian>(VERSION)?;
+        out.write_u64::<BigEndian>(self.rom_hash)?;
+        out.write_all(self.platform.to_string().as_bytes())?;
+        out.write_u8(b'\n')?;
+        out.write_u32::<BigEndian>(self.timing.clock_hz)?;
+        out.write_u32::<BigEndian>(self.timing.timer_hz)?;
+        out.write_u32::<BigEndian>(self.seed)?;
+        for action in &fault_actions(&self.error_policy) {
+            out.write_u8(match *action {
+                FaultAction::Halt => 0,
+                FaultAction::Skip => 1,
+                FaultAction::Nop => 2,
+            })?;
+        }
+
+        out.write_u32::<BigEndian>(self.frames.len() as u32)?;
+        for frame in &self.frames {
+            out.write_u16::<BigEndian>(frame.keys)?;
+            out.write_u32::<BigEndian>(frame.checksum)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut input: R) -> Result<Movie, MovieError> {
+        let mut magic = [0_u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        match input.read_u16::<BigEndian>()? {
+            1 => {}
+            v => return Err(MovieError::UnsupportedVersion(v)),
+        }
+
+        let rom_hash = input.read_u64::<BigEndian>()?;
+        let platform = read_line(&mut input)?
+            .parse()
+            .map_err(|_| MovieError::Corrupt("platform"))?;
+        let timing = Timing {
+            clock_hz: input.read_u32::<BigEndian>()?,
+            timer_hz: input.read_u32::<BigEndian>()?,
+        };
+        let seed = input.read_u32::<BigEndian>()?;
+        let mut actions = [FaultAction::Halt; 6];
+        for action in actions.iter_mut() {
+            *action = match input.read_u8()? {
+                0 => FaultAction::Halt,
+                1 => FaultAction::Skip,
+                2 => FaultAction::Nop,
+                _ => return Err(MovieError::Corrupt("fault policy")),
+            };
+        }
+        let error_policy = ErrorPolicy {
+            invalid_opcode: actions[0],
+            stack_underflow: actions[1],
+            stack_overflow: actions[2],
+            pc_out_of_bounds: actions[3],
+            index_out_of_bounds: actions[4],
+            invalid_key: actions[5],
+        };
+
+        let count = input.read_u32::<BigEndian>()?;
+        let mut frames = vec![];
+        for _ in 0..count {
+            frames.push(MovieFrame {
+                keys: input.read_u16::<BigEndian>()?,
+                checksum: input.read_u32::<BigEndian>()?,
+            });
+        }
+
+        Ok(Movie {
+            rom_hash,
+            platform,
+            timing,
+            seed,
+            error_policy,
+            frames,
+        })
+    }
+}
+
+/// A movie being played back.
+pub struct Playback {
+    pub movie: Movie,
+
+    pub frame: usize, //next frame to play
+
+    pub desync: Option<usize>, //first frame whose screen differed from the recording
+}
+
+impl Playback {
+    pub fn new(movie: Movie) -> Playback {
+        Playback {
+            movie,
+            frame: 0,
+            desync: None,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    /// Sets `keys` to what was held during the next frame.
+    pub fn feed(&self, keys: &mut [u8; 16]) {
+        if let Some(frame) = self.movie.frames.get(self.frame) {
+            unpack_keys(frame.keys, keys);
+        }
+    }
+
+    /// Checks the screen after the frame ran against the recording and moves
+    /// on. Returns false at the first frame that differs.
+    pub fn verify(&mut self, gpu: &GPU) -> bool {
+        let expected = self.movie.frames.get(self.frame).map(|f| f.checksum);
+        self.frame += 1;
+        if self.desync.is_none() && expected != Some(checksum(gpu)) {
+            self.desync = Some(self.frame - 1);
+            return false;
+        }
+        true
+    }
+}
+
+fn read_line<R: Read>(input: &mut R) -> Result<String, MovieError> {
+    let mut line = vec![];
+    loop {
+        match input.read_u8()? {
+            b'\n' => break,
+            b => line.push(b),
+        }
+    }
+    String::from_utf8(line).map_err(|_| MovieError::Corrupt("header"))
+}
+
+/// The policy's actions in the order movies store them.
+fn fault_actions(policy: &ErrorPolicy) -> [FaultAction; 6] {
+    [
+        policy.invalid_opcode,
+        policy.stack_underflow,
+        policy.stack_overflow,
+        policy.pc_out_of_bounds,
+        policy.index_out_of_bounds,
+        policy.invalid_key,
+    ]
+}
+
+fn pack_keys(keys: &[u8; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |bits, (n, k)| bits | (((*k != 0) as u16) << n))
+}
+
+fn unpack_keys(bits: u16, keys: &mut [u8; 16]) {
+    for (n, key) in keys.iter_mut().enumerate() {
+        *key = (bits >> n & 1) as u8;
+    }
+}
+
+/// FNV-1a, 64-bit.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// A 32-bit checksum of the framebuffer and its resolution.
+pub fn checksum(gpu: &GPU) -> u32 {
+    let hash = fnv1a(&gpu.gfx) ^ gpu.hires as u64;
+    (hash ^ hash >> 32) as u32
+}
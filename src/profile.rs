@@ -0,0 +1,356 @@
+//! A hot-spot profiler: how often each address and each kind of instruction
+//! executes, how many instructions run per frame, and which subroutines they
+//! run in.
+//!
+//! Subroutines are named after their entry address like disasm's labels,
+//! e.g. `sub_2F6`, with `main` for code outside any call. The call stacks come
+//! from `CPU::stack`, whose entries are the addresses of the CALLs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::iter;
+use std::mem::{self, Discriminant};
+use std::str::FromStr;
+
+use disasm::{Labels, Line};
+use instructions::Instructions;
+
+/// How a `Report` is written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProfileFormat {
+    #[default]
+    Text,
+
+    Json,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ProfileFormat, String> {
+        match s {
+            "text" => Ok(ProfileFormat::Text),
+            "json" => Ok(ProfileFormat::Json),
+            _ => Err(format!("unknown profile format {}", s)),
+        }
+    }
+}
+
+/// Counts executions for `CPU::profiler`.
+pub struct Profiler {
+    addresses: Vec<u64>, //executions by address
+
+    kinds: HashMap<Discriminant<Instructions>, (Instructions, u64)>, //an example of each for its name
+
+    frame: u64, //instructions so far this frame
+
+    frames: Vec<u64>, //instructions in each finished frame
+
+    stack: Vec<usize>, //CPU::stack as last seen
+
+    entries: Vec<usize>, //the subroutine each call in `stack` went to
+
+    stacks: HashMap<Vec<usize>, u64>, //instructions by the entries of the stack they ran under
+
+    calls: HashMap<(Option<usize>, usize), u64>, //CALLs by caller (None for main) and callee
+}
+
+impl Profiler {
+    /// A profiler for a machine with `memory_size` bytes of memory.
+    pub fn new(memory_size: usize) -> Profiler {
+        Profiler {
+            addresses: vec![0; memory_size],
+            kinds: HashMap::new(),
+            frame: 0,
+            frames: vec![],
+            stack: vec![],
+            entries: vec![],
+            stacks: HashMap::new(),
+            calls: HashMap::new(),
+        }
+    }
+
+    /// Counts `instruction` executing at `pc` under the call `stack`, with
+    /// the CALLs read from `mem`.
+    pub fn count(&mut self, pc: usize, instruction: Instructions, stack: &[usize], mem: &[u8]) {
+        if let Some(n) = self.addresses.get_mut(pc) {
+            *n += 1;
+        }
+
+        if self.stack != stack {
+            self.stack = stack.to_vec();
+            self.entries = stack.iter().map(|&call| entry(mem, call)).collect();
+        }
+        match self.stacks.get_mut(&self.entries[..]) {
+            Some(n) => *n += 1,
+            None => {
+                self.stacks.insert(self.entries.clone(), 1);
+            }
+        }
+        if let Instructions::CallSub(target) = instruction {
+            let caller = self.entries.last().cloned();
+            *self.calls.entry((caller, target as usize)).or_insert(0) += 1;
+        }
+
+        self.kinds
+            .entry(mem::discriminant(&instruction))
+            .or_insert((instruction, 0))
+            .1 += 1;
+        self.frame += 1;
+    }
+
+    /// Closes the current frame's instruction count. Frames where nothing
+    /// ran, as while rewinding, are left out.
+    pub fn end_frame(&mut self) {
+        if self.frame > 0 {
+            self.frames.push(self.frame);
+        }
+        self.frame = 0;
+    }
+
+    /// Summarises the counts, disassembling the `top` hottest addresses
+    /// from `mem`.
+    pub fn report(&self, mem: &[u8], top: usize) -> Report {
+        let total: u64 = self.addresses.iter().sum();
+
+        let mut hot: Vec<(usize, u64)> = self
+            .addresses
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| n > 0)
+            .map(|(addr, &n)| (addr, n))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(top);
+        let addresses = hot
+            .into_iter()
+            .map(|(address, count)| HotAddress {
+                address,
+                count,
+                mnemonic: Line::decode(mem, address).mnemonic(&Labels::new()),
+            })
+            .collect();
+
+        let mut kinds: Vec<(String, u64)> = self
+            .kinds
+            .values()
+            .map(|&(instruction, n)| (kind_name(instruction), n))
+            .collect();
+        kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let frames = self.frames.len() as u64;
+        Report {
+            total,
+            frames,
+            min_per_frame: self.frames.iter().cloned().min().unwrap_or(0),
+            max_per_frame: self.frames.iter().cloned().max().unwrap_or(0),
+            mean_per_frame: match frames {
+                0 => 0.0,
+                n => self.frames.iter().sum::<u64>() as f64 / n as f64,
+            },
+            addresses,
+            kinds,
+        }
+    }
+}
+
+/// The subroutine the CALL at `call` went to. Falls back to `call` itself if
+/// the program has since overwritten it.
+fn entry(mem: &[u8], call: usize) -> usize {
+    match Line::decode(mem, call).instruction {
+        Some(Instructions::CallSub(target)) => target as usize,
+        _ => call,
+    }
+}
+
+fn subroutine_name(entry: Option<usize>) -> String {
+    match entry {
+        Some(entry) => format!("sub_{:03X}", entry),
+        None => "main".to_string(),
+    }
+}
+
+impl Profiler {
+    /// Writes the instructions executed under each call stack in the folded
+    /// format flamegraph tools read: `main;sub_2F6;sub_39A 1234`.
+    pub fn write_folded<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut stacks: Vec<(String, u64)> = self
+            .stacks
+            .iter()
+            .map(|(entries, &n)| {
+                let names: Vec<String> = iter::once(None)
+                    .chain(entries.iter().map(|&e| Some(e)))
+                    .map(subroutine_name)
+                    .collect();
+                (names.join(";"), n)
+            })
+            .collect();
+        stacks.sort();
+        for (stack, n) in stacks {
+            writeln!(out, "{} {}", stack, n)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the call graph in Graphviz DOT. Each subroutine shows the share
+    /// of instructions run in it and in everything it calls; each edge shows
+    /// how often the caller called it.
+    pub fn write_dot<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let total: u64 = self.stacks.values().sum();
+        let percent = |n: u64| match total {
+            0 => 0.0,
+            total => n as f64 * 100.0 / total as f64,
+        };
+
+        //self and inclusive counts; a recursive routine counts once per stack
+        let mut routines: BTreeMap<Option<usize>, (u64, u64)> = BTreeMap::new();
+        routines.insert(None, (0, total));
+        for (entries, &n) in &self.stacks {
+            routines.entry(entries.last().cloned()).or_insert((0, 0)).0 += n;
+            let mut seen: Vec<usize> = entries.clone();
+            seen.sort_unstable();
+            seen.dedup();
+            for entry in seen {
+                routines.entry(Some(entry)).or_insert((0, 0)).1 += n;
+            }
+        }
+        for &(caller, callee) in self.calls.keys() {
+            routines.entry(caller).or_insert((0, 0));
+            routines.entry(Some(callee)).or_insert((0, 0));
+        }
+
+        writeln!(out, "digraph calls {{")?;
+        writeln!(out, "    node [shape=box];")?;
+        for (&entry, &(own, inclusive)) in &routines {
+            writeln!(
+                out,
+                "    \"{}\" [label=\"{}\\nself {:.1}%\\ntotal {:.1}%\"];",
+                subroutine_name(entry),
+                subroutine_name(entry),
+                percent(own),
+                percent(inclusive)
+            )?;
+        }
+        let mut calls: Vec<(&(Option<usize>, usize), &u64)> = self.calls.iter().collect();
+        calls.sort();
+        for (&(caller, callee), n) in calls {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                subroutine_name(caller),
+                subroutine_name(Some(callee)),
+                n
+            )?;
+        }
+        writeln!(out, "}}")
+    }
+}
+
+/// The variant name, e.g. `Display` for DXYN.
+fn kind_name(instruction: Instructions) -> String {
+    let debug = format!("{:?}", instruction);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+/// One of the hottest addresses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotAddress {
+    pub address: usize,
+
+    pub count: u64,
+
+    pub mnemonic: String,
+}
+
+/// What `Profiler::report` found. `Display` gives the text report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub total: u64, //instructions executed
+
+    pub frames: u64,
+
+    pub min_per_frame: u64,
+
+    pub max_per_frame: u64,
+
+    pub mean_per_frame: f64,
+
+    pub addresses: Vec<HotAddress>, //hottest first
+
+    pub kinds: Vec<(String, u64)>, //executions by Instructions variant, most first
+}
+
+impl Report {
+    /// The report as a JSON object.
+    pub fn to_json(&self) -> String {
+        let addresses: Vec<String> = self
+            .addresses
+            .iter()
+            .map(|a| {
+                format!(
+                    "{{\"address\":{},\"count\":{},\"instruction\":\"{}\"}}",
+                    a.address,
+                    a.count,
+                    escape(&a.mnemonic)
+                )
+            })
+            .collect();
+        let kinds: Vec<String> = self
+            .kinds
+            .iter()
+            .map(|(name, n)| format!("\"{}\":{}", name, n))
+            .collect();
+        format!(
+            "{{\"instructions\":{},\"frames\":{},\"per_frame\":{{\"min\":{},\"mean\":{:.2},\"max\":{}}},\"addresses\":[{}],\"kinds\":{{{}}}}}",
+            self.total,
+            self.frames,
+            self.min_per_frame,
+            self.mean_per_frame,
+            self.max_per_frame,
+            addresses.join(","),
+            kinds.join(",")
+        )
+    }
+
+    fn percent(&self, n: u64) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => n as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions over {} frames, {:.1} per frame (min {}, max {})",
+            self.total, self.frames, self.mean_per_frame, self.min_per_frame, self.max_per_frame
+        )?;
+        writeln!(f, "\nhot addresses:")?;
+        for a in &self.addresses {
+            writeln!(
+                f,
+                "  {:04X}  {:>10}  {:5.1}%  {}",
+                a.address,
+                a.count,
+                self.percent(a.count),
+                a.mnemonic
+            )?;
+        }
+        writeln!(f, "\ninstructions by kind:")?;
+        for (name, n) in &self.kinds {
+            writeln!(f, "  {:<20}  {:>10}  {:5.1}%", name, n, self.percent(*n))?;
+        }
+        Ok(())
+    }
+}
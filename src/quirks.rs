@@ -0,0 +1,121 @@
+//! Behaviours that differ between CHIP-8 interpreters.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The behaviours `CPU` chooses between. Each flag names what happens when it
+/// is `true`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift_uses_vy: bool, //8XY6/8XYE shift VY into VX instead of shifting VX in place
+
+    pub load_store_increments_i: bool, //FX55/FX65 leave I pointing past the last register
+
+    pub jump_uses_vx: bool, //BNNN jumps to XNN + VX instead of NNN + V0
+
+    pub logic_resets_vf: bool, //8XY1/8XY2/8XY3 set VF to 0
+
+    pub sprite_wrap: bool, //DXYN wraps pixels past the screen edge instead of clipping them
+
+    pub display_wait: bool, //DXYN waits for the next vblank before drawing
+}
+
+impl Quirks {
+    pub fn cosmac_vip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            sprite_wrap: false,
+            display_wait: true,
+        }
+    }
+
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            sprite_wrap: false,
+            display_wait: false,
+        }
+    }
+
+    pub fn superchip() -> Quirks {
+        Quirks::chip48()
+    }
+
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            sprite_wrap: true,
+            display_wait: false,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::cosmac_vip()
+    }
+}
+
+/// An interpreter whose quirks a ROM may expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    /// Bytes of addressable memory: 4K, or the full 16-bit space on XO-CHIP.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks::cosmac_vip(),
+            Platform::Chip48 => Quirks::chip48(),
+            Platform::SuperChip => Quirks::superchip(),
+            Platform::XoChip => Quirks::xochip(),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Platform::CosmacVip => "vip",
+            Platform::Chip48 => "chip48",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Platform, String> {
+        match s {
+            "vip" | "chip8" => Ok(Platform::CosmacVip),
+            "chip48" => Ok(Platform::Chip48),
+            "schip" | "superchip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!("unknown platform {}", s)),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Rewinding: a bounded history of save states, one per frame.
+//!
+//! Only the newest snapshot is kept whole. Every older one is stored as the
+//! XOR of itself with the snapshot after it, so the unchanged bulk of memory
+//! and the framebuffer turns into runs of zeros, which are then run-length
+//! encoded. Stepping back XORs the newest delta into the current snapshot.
+
+use std::collections::VecDeque;
+
+use cpu::CPU;
+
+/// One step back: the older snapshot XORed with the newer one.
+struct Delta {
+    len: usize, //length of the older snapshot, which changes with the resolution
+
+    data: Vec<u8>, //rle-encoded XOR
+}
+
+pub struct RewindBuffer {
+    capacity: usize, //frames
+
+    latest: Option<Vec<u8>>,
+
+    deltas: VecDeque<Delta>, //oldest first
+}
+
+impl RewindBuffer {
+    /// A buffer holding up to `capacity` frames of history.
+    pub fn new(capacity: usize) -> RewindBuffer {
+        RewindBuffer {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Number of frames that can currently be stepped back.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    /// Records the machine as it is now, dropping the oldest frame when full.
+    pub fn push(&mut self, cpu: &CPU) {
+        let state = cpu.state_bytes();
+        if let Some(prev) = self.latest.take() {
+            self.deltas.push_back(Delta {
+                len: prev.len(),
+                data: rle_encode(&xor(&prev, &state)),
+            });
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// Restores the frame before the last one recorded, returning false once
+    /// the history is used up. Keys are left as the host currently holds them.
+    pub fn step_back(&mut self, cpu: &mut CPU) -> bool {
+        let (delta, latest) = match (self.deltas.pop_back(), self.latest.as_mut()) {
+            (Some(delta), Some(latest)) => (delta, latest),
+            _ => return false,
+        };
+
+        let diff = rle_decode(&delta.data);
+        latest.resize(diff.len(), 0);
+        for (b, d) in latest.iter_mut().zip(diff.iter()) {
+            *b ^= d;
+        }
+        latest.truncate(delta.len);
+
+        let keys = cpu.keys;
+        cpu.load_state(&latest[..])
+            .expect("rewind snapshots are written by save_state");
+        cpu.keys = keys;
+        true
+    }
+}
+
+/// XOR of `a` and `b`, the shorter one padded with zeros.
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| a.get(i).unwrap_or(&0) ^ b.get(i).unwrap_or(&0))
+        .collect()
+}
+
+/// Encodes `data` as repeated (zero run, literal count, literals), with the
+/// counts as LEB128 varints.
+fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = data[i..].iter().take_while(|b| **b == 0).count();
+        i += zeros;
+        let literals = data[i..].iter().take_while(|b| **b != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&data[i..i + literals]);
+        i += literals;
+    }
+    out
+}
+
+fn rle_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = read_varint(data, &mut i);
+        let literals = read_varint(data, &mut i);
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&data[i..i + literals]);
+        i += literals;
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut n = 0;
+    let mut shift = 0;
+    loop {
+        let b = data[*i];
+        *i += 1;
+        n |= ((b & 0x7F) as usize) << shift;
+        if b & 0x80 == 0 {
+            return n;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,43 @@
+//! Random numbers for CXNN.
+//!
+//! Randomness comes from a generator owned by the `CPU`, so a run is
+//! reproducible from its seed and the generator is saved with the machine.
+//!
+//! There is no COSMAC VIP mode. The VIP's CXNN mixes the 1802's R9 register,
+//! which its display interrupt routine advances, with bytes of the interpreter
+//! ROM itself, so its sequence cannot be reproduced without emulating the
+//! 1802 and shipping that ROM.
+
+use rand;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    pub seed: u32, //what the generator started from, kept for reports and save states
+
+    pub state: u32, //xorshift32 state, never 0
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        let state = match seed {
+            0 => 0x9E37_79B9,
+            seed => seed,
+        };
+        Rng { seed, state }
+    }
+
+    /// A generator seeded from the operating system.
+    pub fn from_entropy() -> Rng {
+        Rng::new(rand::random::<u32>())
+    }
+
+    /// The next random byte, from xorshift32.
+    pub fn next_u8(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        (x >> 24) as u8
+    }
+}
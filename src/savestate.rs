@@ -0,0 +1,337 @@
+//! Save states: a snapshot of the whole machine that can be written to disk
+//! and restored later to resume exactly where it left off.
+//!
+//! The format is a 4-byte magic, a big-endian `u16` version and then the
+//! fields of that version in a fixed order. Loading dispatches on the
+//! version, so when the layout changes the old reader stays around and
+//! migrates its result.
+
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::{CPU, STACK_SIZE};
+use gpu::{HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, PLANES, WIDTH};
+use quirks::{Platform, Quirks};
+use rng::Rng;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+
+/// The version `save_state` writes.
+pub const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum StateError {
+    BadMagic,
+
+    UnsupportedVersion(u16),
+
+    Corrupt(&'static str), //a field holds a value the machine cannot be in
+
+    Io(io::Error),
+}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> StateError {
+        StateError::Io(e)
+    }
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(v) => {
+                write!(f, "save state version {} is newer than {}", v, VERSION)
+            }
+            StateError::Corrupt(what) => write!(f, "corrupt save state: bad {}", what),
+            StateError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for StateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            StateError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl CPU {
+    /// Writes everything needed to resume this machine. The error policy is a
+    /// host setting and is not saved.
+    pub fn save_state<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_u16::<BigEndian>(VERSION)?;
+
+        out.write_u8(platform_id(self.platform))?;
+        out.write_u8(quirk_bits(self.quirks))?;
+
+        out.write_u32::<BigEndian>(self.pc as u32)?;
+        out.write_u16::<BigEndian>(self.index_reg)?;
+        out.write_all(&self.registers)?;
+        out.write_u8(self.delay_timer)?;
+        out.write_u8(self.sound_timer)?;
+        out.write_u8(self.stack.len() as u8)?;
+        for addr in &self.stack {
+            out.write_u32::<BigEndian>(*addr as u32)?;
+        }
+
+        out.write_all(&self.keys)?;
+        out.write_all(&self.rpl)?;
+        out.write_u8(self.halted as u8)?;
+        out.write_u8(self.vblank as u8)?;
+        match self.audio_pattern {
+            Some(pattern) => {
+                out.write_u8(1)?;
+                out.write_all(&pattern)?;
+            }
+            None => out.write_u8(0)?,
+        }
+        out.write_u8(self.pitch)?;
+        out.write_u32::<BigEndian>(self.rng.seed)?;
+        out.write_u32::<BigEndian>(self.rng.state)?;
+
+        out.write_u8(self.gpu.hires as u8)?;
+        out.write_u8(self.gpu.planes)?;
+        out.write_all(&self.gpu.gfx)?;
+
+        out.write_all(self.mem.get_ref())
+    }
+
+    /// Restores a state written by `save_state`. On error the machine is left
+    /// unchanged.
+    pub fn load_state<R: Read>(&mut self, mut input: R) -> Result<(), StateError> {
+        let mut magic = [0_u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let state = match input.read_u16::<BigEndian>()? {
+            1 => read_state(&mut input)?,
+            v => return Err(StateError::UnsupportedVersion(v)),
+        };
+        state.apply(self);
+        Ok(())
+    }
+
+    /// Snapshot of the machine as a byte vector.
+    pub fn state_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.save_state(&mut buf)
+            .expect("writing to a Vec cannot fail");
+        buf
+    }
+}
+
+/// A decoded save state, checked before anything is written to the CPU.
+struct State {
+    platform: Platform,
+    quirks: Quirks,
+    pc: usize,
+    index_reg: u16,
+    registers: [u8; 16],
+    delay_timer: u8,
+    sound_timer: u8,
+    stack: Vec<usize>,
+    keys: [u8; 16],
+    rpl: [u8; 16],
+    halted: bool,
+    vblank: bool,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    rng: Rng,
+    hires: bool,
+    planes: u8,
+    gfx: Vec<u8>,
+    mem: Vec<u8>,
+}
+
+impl State {
+    fn apply(self, cpu: &mut CPU) {
+        cpu.platform = self.platform;
+        cpu.quirks = self.quirks;
+        cpu.pc = self.pc;
+        cpu.index_reg = self.index_reg;
+        cpu.registers = self.registers;
+        cpu.delay_timer = self.delay_timer;
+        cpu.sound_timer = self.sound_timer;
+        cpu.sp = self.stack.len();
+        cpu.stack = self.stack;
+        cpu.keys = self.keys;
+        cpu.rpl = self.rpl;
+        cpu.halted = self.halted;
+        cpu.vblank = self.vblank;
+        cpu.audio_pattern = self.audio_pattern;
+        cpu.pitch = self.pitch;
+        cpu.rng = self.rng;
+        cpu.gpu.hires = self.hires;
+        cpu.gpu.planes = self.planes;
+        cpu.gpu.gfx = self.gfx;
+        *cpu.mem.get_mut() = self.mem;
+    }
+}
+
+/// Reads the fields of a version 1 state.
+fn read_state<R: Read>(input: &mut R) -> Result<State, StateError> {
+    let platform = match input.read_u8()? {
+        0 => Platform::CosmacVip,
+        1 => Platform::Chip48,
+        2 => Platform::SuperChip,
+        3 => Platform::XoChip,
+        _ => return Err(StateError::Corrupt("platform")),
+    };
+    let quirks = quirks_from_bits(input.read_u8()?);
+
+    let pc = input.read_u32::<BigEndian>()? as usize;
+    let index_reg = input.read_u16::<BigEndian>()?;
+    let mut registers = [0_u8; 16];
+    input.read_exact(&mut registers)?;
+    let delay_timer = input.read_u8()?;
+    let sound_timer = input.read_u8()?;
+    let depth = input.read_u8()? as usize;
+    if depth > STACK_SIZE {
+        return Err(StateError::Corrupt("stack depth"));
+    }
+    let mut stack = Vec::with_capacity(depth);
+    for _ in 0..depth {
+        stack.push(input.read_u32::<BigEndian>()? as usize);
+    }
+
+    let mut keys = [0_u8; 16];
+    input.read_exact(&mut keys)?;
+    let mut rpl = [0_u8; 16];
+    input.read_exact(&mut rpl)?;
+    let halted = input.read_u8()? != 0;
+    let vblank = input.read_u8()? != 0;
+    let audio_pattern = match input.read_u8()? {
+        0 => None,
+        _ => {
+            let mut pattern = [0_u8; 16];
+            input.read_exact(&mut pattern)?;
+            Some(pattern)
+        }
+    };
+    let pitch = input.read_u8()?;
+    let seed = input.read_u32::<BigEndian>()?;
+    let rng = Rng {
+        seed,
+        state: input.read_u32::<BigEndian>()?,
+    };
+
+    let hires = input.read_u8()? != 0;
+    let planes = input.read_u8()?;
+    if planes >> PLANES != 0 {
+        return Err(StateError::Corrupt("plane mask"));
+    }
+    let pixels = if hires {
+        HIRES_WIDTH * HIRES_HEIGHT
+    } else {
+        WIDTH * HEIGHT
+    };
+    let mut gfx = vec![0_u8; pixels];
+    input.read_exact(&mut gfx)?;
+
+    let mut mem = vec![0_u8; platform.memory_size()];
+    input.read_exact(&mut mem)?;
+    if pc >= mem.len() {
+        return Err(StateError::Corrupt("program counter"));
+    }
+    if stack.iter().any(|addr| *addr >= mem.len()) {
+        return Err(StateError::Corrupt("stack"));
+    }
+
+    Ok(State {
+        platform,
+        quirks,
+        pc,
+        index_reg,
+        registers,
+        delay_timer,
+        sound_timer,
+        stack,
+        keys,
+        rpl,
+        halted,
+        vblank,
+        audio_pattern,
+        pitch,
+        rng,
+        hires,
+        planes,
+        gfx,
+        mem,
+    })
+}
+
+fn platform_id(platform: Platform) -> u8 {
+    match platform {
+        Platform::CosmacVip => 0,
+        Platform::Chip48 => 1,
+        Platform::SuperChip => 2,
+        Platform::XoChip => 3,
+    }
+}
+
+fn quirk_bits(q: Quirks) -> u8 {
+    [
+        q.shift_uses_vy,
+        q.load_store_increments_i,
+        q.jump_uses_vx,
+        q.logic_resets_vf,
+        q.sprite_wrap,
+        q.display_wait,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (n, set)| bits | ((*set as u8) << n))
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let set = |n: u8| bits & (1 << n) != 0;
+    Quirks {
+        shift_uses_vy: set(0),
+        load_store_increments_i: set(1),
+        jump_uses_vx: set(2),
+        logic_resets_vf: set(3),
+        sprite_wrap: set(4),
+        display_wait: set(5),
+    }
+}
+
+/// Numbered save slots stored next to the ROM as `<rom>.state<N>`.
+#[derive(Debug, Clone)]
+pub struct SaveSlots {
+    base: PathBuf,
+}
+
+impl SaveSlots {
+    pub fn for_rom<P: AsRef<Path>>(rom_path: P) -> SaveSlots {
+        SaveSlots {
+            base: rom_path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self, slot: u8) -> PathBuf {
+        let mut name = self.base.clone().into_os_string();
+        name.push(format!(".state{}", slot));
+        PathBuf::from(name)
+    }
+
+    pub fn save(&self, cpu: &CPU, slot: u8) -> Result<(), StateError> {
+        let mut file = File::create(self.path(slot))?;
+        file.write_all(&cpu.state_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(&self, cpu: &mut CPU, slot: u8) -> Result<(), StateError> {
+        cpu.load_state(File::open(self.path(slot))?)
+    }
+}
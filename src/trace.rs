@@ -0,0 +1,432 @@
+//! Instruction traces: one line per executed instruction, for diffing runs
+//! against each other or against other emulators.
+//!
+//! Each line shows the machine after the fetch and before the instruction
+//! executes, then the memory the instruction wrote, in fixed-width fields:
+//!
+//! ```text
+//! 00000001 0200 A2EA LD I, 0x2EA          V=00000000000000000000000000000000 I=0000 SP=0 DT=00 ST=00 W=
+//! 00000107 0310 F233 LD B, V2             V=80000000000000000000000000000000 I=0400 SP=1 DT=00 ST=00 W=0400:01,0401:02,0402:08
+//! ```
+//!
+//! That is the cycle number (decimal, from 1), PC, opcode, the mnemonic padded
+//! to 20 characters, V0 to VF, I, SP, the two timers and the writes as
+//! `ADDR:BYTE` pairs, all but the cycle in hex. The format only ever grows new
+//! fields at the end of the line.
+//!
+//! # Importing traces
+//!
+//! `diff` reads any trace whose lines start with the cycle, PC and opcode
+//! separated by whitespace, followed by any of the `V=`, `I=`, `SP=`, `DT=`,
+//! `ST=` and `W=` fields above in any order. Other words, like the mnemonic,
+//! are ignored, as are blank lines and lines starting with `#`. A field
+//! missing from either trace is not compared, so another emulator only needs
+//! to print what it has, e.g.
+//!
+//! ```text
+//! 1 200 A2EA I=0 V=00000000000000000000000000000000
+//! ```
+
+use std::collections::VecDeque;
+use std::error;
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, BufRead, Write};
+
+use cpu::CPU;
+use disasm::{Labels, Line};
+
+/// Which instructions a `Tracer` writes. Empty lists let everything through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub ranges: Vec<(usize, usize)>, //inclusive PC ranges
+
+    pub mnemonics: Vec<String>, //e.g. "DRW", matched case-insensitively
+}
+
+impl TraceFilter {
+    /// Whether the instruction at `pc` with `mnemonic` should be traced.
+    pub fn matches(&self, pc: usize, mnemonic: &str) -> bool {
+        self.in_range(pc) && self.of_kind(mnemonic)
+    }
+
+    /// Whether `pc` lies in one of the ranges.
+    pub fn in_range(&self, pc: usize) -> bool {
+        self.ranges.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|&(start, end)| start <= pc && pc <= end)
+    }
+
+    /// Whether `mnemonic` is one of the instructions asked for.
+    pub fn of_kind(&self, mnemonic: &str) -> bool {
+        let name = mnemonic.split_whitespace().next().unwrap_or("");
+        self.mnemonics.is_empty() || self.mnemonics.iter().any(|m| m.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Writes trace lines for `CPU::tracer`.
+pub struct Tracer {
+    out: Box<dyn Write + Send>,
+
+    pub filter: TraceFilter,
+
+    pending: Option<String>, //the current instruction's line, waiting for its writes
+
+    error: Option<io::Error>, //the first write error; tracing stops there
+}
+
+impl Tracer {
+    pub fn new<W: Write + Send + 'static>(out: W, filter: TraceFilter) -> Tracer {
+        Tracer {
+            out: Box::new(out),
+            filter,
+            pending: None,
+            error: None,
+        }
+    }
+
+    /// Traces the instruction `cpu` just fetched.
+    pub fn trace(&mut self, cpu: &CPU, opcode: u16) {
+        self.flush_line();
+        //most of a ranged trace is skipped, so only disassemble what is in range
+        if self.error.is_some() || !self.filter.in_range(cpu.pc) {
+            return;
+        }
+        let line = Line::decode(cpu.mem.get_ref(), cpu.pc);
+        let mnemonic = line.mnemonic(&Labels::new());
+        if !self.filter.of_kind(&mnemonic) {
+            return;
+        }
+
+        let mut text = format!(
+            "{:08} {:04X} {:04X} {:<20} V=",
+            cpu.cycles, cpu.pc, opcode, mnemonic
+        );
+        for v in &cpu.registers {
+            write!(text, "{:02X}", v).unwrap();
+        }
+        write!(
+            text,
+            " I={:04X} SP={:X} DT={:02X} ST={:02X} W=",
+            cpu.index_reg, cpu.sp, cpu.delay_timer, cpu.sound_timer
+        )
+        .unwrap();
+        self.pending = Some(text);
+    }
+
+    /// Notes that the instruction being traced stored `value` at `address`.
+    pub fn write(&mut self, address: usize, value: u8) {
+        if let Some(ref mut text) = self.pending {
+            if !text.ends_with('=') {
+                text.push(',');
+            }
+            write!(text, "{:04X}:{:02X}", address, value).unwrap();
+        }
+    }
+
+    /// Flushes the output, reporting the first error writing the trace.
+    /// Dropping a `Tracer` flushes too, but cannot report errors.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush_line();
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    fn flush_line(&mut self) {
+        if let Some(text) = self.pending.take() {
+            if let Err(e) = writeln!(self.out, "{}", text) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+impl Drop for Tracer {
+    //the last instruction's line waits for its writes, so it must not be lost
+    fn drop(&mut self) {
+        self.flush_line();
+        if self.error.is_none() {
+            let _ = self.out.flush();
+        }
+    }
+}
+
+/// Why a trace could not be read.
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TraceError::Io(ref e) => write!(f, "{}", e),
+            TraceError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl error::Error for TraceError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            TraceError::Io(ref e) => Some(e),
+            TraceError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(error: io::Error) -> Self {
+        TraceError::Io(error)
+    }
+}
+
+/// One parsed trace line. Fields the line did not have are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub text: String, //the line as read
+
+    pub cycle: u64,
+
+    pub pc: usize,
+
+    pub opcode: u16,
+
+    pub registers: Option<[u8; 16]>,
+
+    pub index: Option<u16>,
+
+    pub sp: Option<usize>,
+
+    pub delay_timer: Option<u8>,
+
+    pub sound_timer: Option<u8>,
+
+    pub writes: Option<Vec<(usize, u8)>>,
+}
+
+impl Record {
+    /// Parses a line in the format described in the module docs.
+    pub fn parse(text: &str) -> Result<Record, String> {
+        let mut words = text.split_whitespace();
+        let mut next = |what: &str| {
+            words
+                .next()
+                .ok_or_else(|| format!("missing {}", what))
+                .map(str::to_string)
+        };
+        let cycle = next("cycle")?;
+        let (pc, opcode) = (next("PC")?, next("opcode")?);
+        let mut record = Record {
+            text: text.to_string(),
+            cycle: cycle.parse().map_err(|_| format!("bad cycle {}", cycle))?,
+            pc: hex(&pc)? as usize,
+            opcode: hex(&opcode)? as u16,
+            registers: None,
+            index: None,
+            sp: None,
+            delay_timer: None,
+            sound_timer: None,
+            writes: None,
+        };
+
+        for word in words {
+            let (key, value) = match word.find('=') {
+                Some(i) => (&word[..i], &word[i + 1..]),
+                None => continue,
+            };
+            match key {
+                "V" => {
+                    let mut registers = [0; 16];
+                    if value.len() != 32 {
+                        return Err(format!("V= needs 32 hex digits, got {}", value));
+                    }
+                    for (n, v) in registers.iter_mut().enumerate() {
+                        *v = hex(&value[n * 2..n * 2 + 2])? as u8;
+                    }
+                    record.registers = Some(registers);
+                }
+                "I" => record.index = Some(hex(value)? as u16),
+                "SP" => record.sp = Some(hex(value)? as usize),
+                "DT" => record.delay_timer = Some(hex(value)? as u8),
+                "ST" => record.sound_timer = Some(hex(value)? as u8),
+                "W" => {
+                    let writes = value
+                        .split(',')
+                        .filter(|w| !w.is_empty())
+                        .map(|w| match w.find(':') {
+                            Some(i) => Ok((hex(&w[..i])? as usize, hex(&w[i + 1..])? as u8)),
+                            None => Err(format!("bad write {}, expected ADDR:BYTE", w)),
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+                    record.writes = Some(writes);
+                }
+                _ => {}
+            }
+        }
+        Ok(record)
+    }
+
+    /// The names of the compared fields that differ from `other`'s. Timers
+    /// are left out, as they depend on the clock settings.
+    pub fn differences(&self, other: &Record) -> Vec<String> {
+        let mut fields = vec![];
+        if self.pc != other.pc {
+            fields.push("PC".to_string());
+        }
+        if self.opcode != other.opcode {
+            fields.push("opcode".to_string());
+        }
+        if let (Some(a), Some(b)) = (self.registers, other.registers) {
+            for n in 0..16 {
+                if a[n] != b[n] {
+                    fields.push(format!("V{:X}", n));
+                }
+            }
+        }
+        let mut compare = |name: &str, differs: Option<bool>| {
+            if differs == Some(true) {
+                fields.push(name.to_string());
+            }
+        };
+        compare("I", both(self.index, other.index));
+        compare("SP", both(self.sp, other.sp));
+        compare("writes", both(self.writes.as_ref(), other.writes.as_ref()));
+        fields
+    }
+}
+
+fn both<T: PartialEq>(a: Option<T>, b: Option<T>) -> Option<bool> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a != b),
+        _ => None,
+    }
+}
+
+fn hex(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|_| format!("bad hex {}", s))
+}
+
+/// Reads the records of a trace.
+pub fn records<R: BufRead>(input: R) -> impl Iterator<Item = Result<Record, TraceError>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| match *line {
+            Ok(ref line) => !line.trim().is_empty() && !line.starts_with('#'),
+            Err(_) => true,
+        })
+        .map(|(n, line)| {
+            let line = line?;
+            Record::parse(&line).map_err(|message| TraceError::Parse {
+                line: n + 1,
+                message,
+            })
+        })
+}
+
+/// Where two traces part ways.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub record: usize, //0-based index of the first differing record
+
+    pub fields: Vec<String>, //what differs; empty when one trace ended early
+
+    pub before: Vec<Record>, //matching records leading up to it, from the first trace
+
+    pub after: (Vec<Record>, Vec<Record>), //from the divergence on, from each trace
+}
+
+/// Finds the first record where the traces differ, keeping `context`
+/// records on either side of it.
+pub fn diff<A, B>(a: A, b: B, context: usize) -> Result<Option<Divergence>, TraceError>
+where
+    A: IntoIterator<Item = Result<Record, TraceError>>,
+    B: IntoIterator<Item = Result<Record, TraceError>>,
+{
+    let (mut a, mut b) = (a.into_iter(), b.into_iter());
+    let mut before = VecDeque::with_capacity(context + 1);
+    let mut index = 0;
+    loop {
+        let (ra, rb) = match (a.next().transpose()?, b.next().transpose()?) {
+            (None, None) => return Ok(None),
+            pair => pair,
+        };
+        let fields = match (ra.as_ref(), rb.as_ref()) {
+            (Some(ra), Some(rb)) => ra.differences(rb),
+            _ => vec![],
+        };
+        let ended = ra.is_none() || rb.is_none();
+        if !fields.is_empty() || ended {
+            let after = (
+                take_context(ra, &mut a, context)?,
+                take_context(rb, &mut b, context)?,
+            );
+            return Ok(Some(Divergence {
+                record: index,
+                fields,
+                before: before.into_iter().collect(),
+                after,
+            }));
+        }
+
+        before.push_back(ra.unwrap());
+        if before.len() > context {
+            before.pop_front();
+        }
+        index += 1;
+    }
+}
+
+/// `first` and up to `context` more records.
+fn take_context<I>(
+    first: Option<Record>,
+    rest: &mut I,
+    context: usize,
+) -> Result<Vec<Record>, TraceError>
+where
+    I: Iterator<Item = Result<Record, TraceError>>,
+{
+    let mut records: Vec<Record> = first.into_iter().collect();
+    while !records.is_empty() && records.len() <= context {
+        match rest.next().transpose()? {
+            Some(record) => records.push(record),
+            None => break,
+        }
+    }
+    Ok(records)
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (ref a, ref b) = self.after;
+        match (a.first(), b.first()) {
+            (Some(first), Some(_)) => writeln!(
+                f,
+                "traces diverge at record {}, cycle {}: {}",
+                self.record + 1,
+                first.cycle,
+                self.fields.join(", ")
+            )?,
+            (None, _) => writeln!(f, "the first trace ends after {} records", self.record)?,
+            (_, None) => writeln!(f, "the second trace ends after {} records", self.record)?,
+        }
+        for record in &self.before {
+            writeln!(f, "  {}", record.text)?;
+        }
+        for n in 0..a.len().max(b.len()) {
+            if let Some(record) = a.get(n) {
+                writeln!(f, "a {}", record.text)?;
+            }
+            if let Some(record) = b.get(n) {
+                writeln!(f, "b {}", record.text)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+extern crate chip8;
+
+use std::convert::TryFrom;
+
+use chip8::asm::{self, Assembly};
+use chip8::disasm::{self, Line};
+use chip8::Instructions;
+
+const ROMS: &[(&str, &[u8])] = &[
+    ("INVADERS", include_bytes!("../roms/INVADERS")),
+    ("PONG2", include_bytes!("../roms/PONG2")),
+    ("TANK", include_bytes!("../roms/TANK")),
+    ("TETRIS", include_bytes!("../roms/TETRIS")),
+    ("TICTAC", include_bytes!("../roms/TICTAC")),
+    ("UFO", include_bytes!("../roms/UFO")),
+];
+
+fn assemble(source: &str) -> Assembly {
+    match asm::assemble(source) {
+        Ok(assembly) => assembly,
+        Err(e) => panic!("{}\n{}", e, source),
+    }
+}
+
+fn error(source: &str) -> String {
+    match asm::assemble(source) {
+        Ok(assembly) => panic!("assembled to {:02X?}", assembly.rom),
+        Err(e) => e.message,
+    }
+}
+
+/// The disassembly of `rom` as assembler source: the listing without its
+/// address and byte columns.
+fn source_of(rom: &[u8]) -> String {
+    let listing = disasm::disassemble(rom);
+    let mut source = String::new();
+    for line in &listing.lines {
+        if let Some(label) = listing.labels.get(&line.address) {
+            source += &format!("{}:\n", label);
+        }
+        source += &format!("    {}\n", line.mnemonic(&listing.labels));
+    }
+    source
+}
+
+#[test]
+fn disassembled_roms_assemble_to_the_same_bytes() {
+    for &(name, rom) in ROMS {
+        assert!(assemble(&source_of(rom)).rom == rom, "{} differs", name);
+    }
+}
+
+#[test]
+fn assembled_programs_survive_disassembly() {
+    let source = r#"
+        SPEED = 3
+    start:
+        CLS
+        LD V0, SPEED
+        LD I, sprite
+        DRW V0, V1, 5
+        CALL move
+        SKP V2
+        JP start
+        JP V0, table
+    move:
+        ADD V0, -1
+        SHR V3, V4
+        LD B, V0
+        LD [I], V2
+        LD I, LONG far
+        RET
+    table:
+        DB 1, 2, 3
+    sprite:
+        DW 0xF090, 0x90F0
+        ORG 0x400
+    far:
+        DB "end"
+    "#;
+    let first = assemble(source).rom;
+    let second = assemble(&source_of(&first)).rom;
+    assert_eq!(second, first);
+}
+
+#[test]
+fn every_decodable_word_assembles_from_its_mnemonic() {
+    for opcode in 0..=0xFFFF_u16 {
+        let instruction = match Instructions::try_from(opcode) {
+            Ok(Instructions::LongIndex) | Err(_) => continue,
+            Ok(instruction) => instruction,
+        };
+        let mnemonic = instruction.to_string();
+        let rom = assemble(&mnemonic).rom;
+        assert_eq!(rom, opcode.to_be_bytes(), "{}", mnemonic);
+
+        let decoded = Line::decode(&[&[0; 0x200][..], &rom[..]].concat(), 0x200);
+        assert_eq!(decoded.instruction, Some(instruction));
+    }
+}
+
+#[test]
+fn constants_and_labels() {
+    let assembly = assemble(
+        "
+        WIDTH = 64
+        HALF EQU WIDTH / 2
+        SIZE = end - start    ; labels can be used before they are defined
+    start:
+        LD V0, HALF
+        LD V1, SIZE
+    end:
+        ",
+    );
+    assert_eq!(assembly.rom, [0x60, 0x20, 0x61, 0x04]);
+    assert_eq!(assembly.labels["start"], 0x200);
+    assert_eq!(assembly.labels["end"], 0x204);
+}
+
+#[test]
+fn constants_are_evaluated_once() {
+    //each constant uses the one before twice, so evaluating them afresh at
+    //every use would take 2^60 steps
+    let mut source = "C0 = 1\n".to_string();
+    for n in 1..=60 {
+        source += &format!("C{} = C{} + C{}\n", n, n - 1, n - 1);
+    }
+    source += "DW C60 >> 48\n";
+    assert_eq!(assemble(&source).rom, [0x10, 0x00]);
+}
+
+#[test]
+fn symbol_files_list_labels_by_address() {
+    let assembly = assemble("SPEED = 2\nstart: JP end\nmiddle: CLS\nend: JP start");
+    let mut sym = vec![];
+    assembly.write_symbols(&mut sym).unwrap();
+    assert_eq!(
+        String::from_utf8(sym).unwrap(),
+        "0200 start\n0202 middle\n0204 end\n"
+    );
+}
+
+#[test]
+fn dollar_in_a_constant_is_where_it_is_defined() {
+    let rom = assemble("X = $\n CLS\n CLS\n JP X\n JP $").rom;
+    assert_eq!(rom, [0x00, 0xE0, 0x00, 0xE0, 0x12, 0x00, 0x12, 0x06]);
+}
+
+#[test]
+fn expressions() {
+    let rom = assemble(
+        "
+        DB 1 + 2 * 3, (1 + 2) * 3, 7 / 2, 7 % 2
+        DB 1 << 4 | 1, 0xF0 & ~0x30, 6 ^ 3, 0x80 >> 3
+        DB -1, 'A', 0b1010_0101, 10 - 2 - 3
+        ",
+    )
+    .rom;
+    assert_eq!(rom, [7, 9, 3, 1, 0x11, 0xC0, 5, 0x10, 0xFF, b'A', 0xA5, 5]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("JP nowhere"), "undefined symbol nowhere");
+    assert_eq!(error("LD V0, 0x100"), "byte 256 is out of range");
+    assert_eq!(error("DB 1 / 0"), "division by zero");
+    assert_eq!(
+        error("DB (-9223372036854775807 - 1) / -1"),
+        "division overflows"
+    );
+    assert_eq!(
+        error("X = Y\nY = X\nDB X"),
+        "X is defined in terms of itself"
+    );
+    assert_eq!(error("x:\nx:"), "x is already defined");
+    assert_eq!(error("ORG 0x300\nORG 0x200"), "ORG 0x200 goes backwards");
+    assert_eq!(error("JP 0x1000"), "address 0x1000 does not fit in 12 bits");
+    assert_eq!(error("DRW V0, V1, 16"), "16 does not fit in 4 bits");
+}
+
+#[test]
+fn operand_names_cannot_be_symbols() {
+    for name in &["f", "B", "r", "k", "i", "dt", "ST", "hf", "long", "v3"] {
+        let message = format!("{} is a register or operand name", name);
+        assert_eq!(error(&format!("{}: CLS", name)), message);
+        assert_eq!(error(&format!("{} = 1", name)), message);
+    }
+    assert_eq!(assemble("fx: JP fx").rom, [0x12, 0x00]);
+}
+
+#[test]
+fn errors_name_the_line() {
+    let e = asm::assemble("CLS\n\n  LD V0, nowhere").unwrap_err();
+    assert_eq!(e.line, 3);
+    assert!(e.to_string().ends_with(":3: undefined symbol nowhere"));
+}
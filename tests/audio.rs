@@ -0,0 +1,68 @@
+extern crate chip8;
+
+use chip8::audio::ToneGenerator;
+use chip8::audio::{AudioConfig, Beeper, WavRecorder};
+use chip8::frontend::{Audio, Timing};
+use chip8::CPU;
+
+const SAMPLE_RATE: u32 = 6000; //100 samples a frame
+
+#[test]
+fn wav_recordings_follow_the_sound_timer() {
+    //LD V0, 03; LD ST, V0; JP 204
+    let mut cpu = CPU::from_bytes(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+    let mut wav = WavRecorder::new(AudioConfig::default(), SAMPLE_RATE);
+    for _ in 0..5 {
+        cpu.run_frame(Timing::default()).unwrap();
+        wav.update(&Beeper::from_cpu(&cpu));
+    }
+
+    //ST is 2 after the first frame's tick and 1 after the second's
+    assert_eq!(wav.samples.len(), 500);
+    assert!(wav.samples[..200].iter().all(|&s| s != 0));
+    assert!(wav.samples[200..].iter().all(|&s| s == 0));
+}
+
+#[test]
+fn muted_wav_recordings_are_silent() {
+    let mut wav = WavRecorder::new(AudioConfig::default(), SAMPLE_RATE);
+    wav.toggle_mute();
+    let beeper = Beeper {
+        on: true,
+        ..Beeper::default()
+    };
+    wav.update(&beeper);
+    assert_eq!(wav.samples, vec![0; 100]);
+}
+
+#[test]
+fn wav_files_have_a_pcm_header() {
+    let mut wav = WavRecorder::new(AudioConfig::default(), SAMPLE_RATE);
+    wav.update(&Beeper::default());
+    let mut file = vec![];
+    wav.write_wav(&mut file).unwrap();
+
+    assert_eq!(file.len(), 44 + 200);
+    assert_eq!(&file[..4], b"RIFF");
+    assert_eq!(&file[8..16], b"WAVEfmt ");
+    assert_eq!(&file[24..28], &SAMPLE_RATE.to_le_bytes());
+    assert_eq!(&file[36..40], b"data");
+    assert_eq!(&file[40..44], &200_u32.to_le_bytes());
+}
+
+#[test]
+fn patterns_play_their_bits() {
+    let mut tone = ToneGenerator::new(AudioConfig::default(), 4000);
+    let mut pattern = [0; 16];
+    pattern[0] = 0xF0;
+    tone.beeper = Beeper {
+        on: true,
+        pattern: Some(pattern),
+        ..Beeper::default()
+    };
+    //pitch 64 plays 4000 bits a second, one per sample here
+    let volume = AudioConfig::default().volume;
+    let samples: Vec<f32> = (0..8).map(|_| tone.next_sample()).collect();
+    assert_eq!(samples[..4], [volume; 4]);
+    assert_eq!(samples[4..], [-volume; 4]);
+}
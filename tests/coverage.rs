@@ -0,0 +1,162 @@
+extern crate chip8;
+
+use chip8::coverage::{self, Coverage, Region};
+use chip8::CPU;
+
+//LD I, 0x20A; LD [I], V1; LD V1, [I]; DRW V0, V0, 1; JP 0x208; then data
+const PROGRAM: [u8; 13] = [
+    0xA2, 0x0A, 0xF1, 0x55, 0xF1, 0x65, 0xD0, 0x01, 0x12, 0x08, 0x00, 0x00, 0xFF,
+];
+
+fn covered() -> CPU {
+    let mut cpu = CPU::from_bytes(&PROGRAM).unwrap();
+    cpu.quirks.load_store_increments_i = false;
+    cpu.quirks.display_wait = false;
+    cpu.registers[1] = 0x5A;
+    cpu.coverage = Some(Coverage::new(cpu.mem.get_ref().len()));
+    for _ in 0..6 {
+        cpu.emulate_cycle().unwrap();
+    }
+    cpu
+}
+
+#[test]
+fn accesses_are_flagged() {
+    let cpu = covered();
+    let coverage = cpu.coverage.as_ref().unwrap();
+    for address in 0x200..0x20A {
+        assert_eq!(coverage.flags(address), coverage::EXECUTED, "{:X}", address);
+    }
+    //FX55 wrote both bytes, FX65 read them back and DXYN read the first
+    assert_eq!(coverage.flags(0x20A), coverage::READ | coverage::WRITTEN);
+    assert_eq!(coverage.flags(0x20B), coverage::READ | coverage::WRITTEN);
+    assert_eq!(coverage.flags(0x20C), 0);
+    assert_eq!(coverage.flags(0x10000), 0);
+}
+
+#[test]
+fn sprites_are_read_through_i() {
+    //LD I, 0x206; DRW V0, V0, 2
+    let mut cpu = CPU::from_bytes(&[0xA2, 0x06, 0xD0, 0x02, 0x00, 0x00, 0xFF, 0x81]).unwrap();
+    cpu.quirks.display_wait = false;
+    cpu.coverage = Some(Coverage::new(cpu.mem.get_ref().len()));
+    cpu.emulate_cycle().unwrap();
+    cpu.emulate_cycle().unwrap();
+    let coverage = cpu.coverage.as_ref().unwrap();
+    assert_eq!(coverage.flags(0x205), 0);
+    assert_eq!(coverage.flags(0x206), coverage::READ);
+    assert_eq!(coverage.flags(0x207), coverage::READ);
+}
+
+#[test]
+fn long_i_covers_its_operand() {
+    let mut cpu = CPU::from_bytes(&[0xF0, 0x00, 0x12, 0x34]).unwrap();
+    cpu.coverage = Some(Coverage::new(cpu.mem.get_ref().len()));
+    cpu.emulate_cycle().unwrap();
+    let coverage = cpu.coverage.as_ref().unwrap();
+    for address in 0x200..0x204 {
+        assert_eq!(coverage.flags(address), coverage::EXECUTED);
+    }
+}
+
+#[test]
+fn report() {
+    let cpu = covered();
+    let report = cpu.coverage.as_ref().unwrap().report(PROGRAM.len());
+    assert_eq!((report.start, report.end), (0x200, 0x20D));
+    assert_eq!(
+        (
+            report.executed,
+            report.read,
+            report.written,
+            report.untouched
+        ),
+        (10, 2, 2, 1)
+    );
+    assert_eq!(
+        report.regions,
+        [
+            Region {
+                start: 0x200,
+                end: 0x20A,
+                flags: coverage::EXECUTED
+            },
+            Region {
+                start: 0x20A,
+                end: 0x20C,
+                flags: coverage::READ | coverage::WRITTEN
+            },
+            Region {
+                start: 0x20C,
+                end: 0x20D,
+                flags: 0
+            },
+        ]
+    );
+    assert_eq!(
+        report.to_string(),
+        "0200-020C, 13 bytes
+  executed      10   76.9%
+  read           2   15.4%
+  written        2   15.4%
+  untouched      1    7.7%
+
+regions (first-last, bytes, access):
+  0200-0209     10  executed
+  020A-020B      2  read, written
+  020C-020C      1  untouched
+"
+    );
+}
+
+#[test]
+fn reports_reach_the_last_byte_touched() {
+    let mut coverage = Coverage::new(0x1000);
+    coverage.mark(0x300, 2, coverage::WRITTEN);
+    let report = coverage.report(4);
+    assert_eq!((report.start, report.end), (0x200, 0x302));
+    assert_eq!((report.written, report.untouched), (2, 0x100));
+
+    assert_eq!(coverage.report(0x1000).end, 0x1000, "clamped to memory");
+}
+
+#[test]
+fn hex_dumps_mark_each_byte() {
+    let cpu = covered();
+    let mut out = vec![];
+    cpu.coverage
+        .as_ref()
+        .unwrap()
+        .write_hexdump(cpu.mem.get_ref(), PROGRAM.len(), &mut out)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "; x executed, r read via I, w written, X executed and read or written,
+; m read and written, . untouched
+0200  A2 0A F1 55 F1 65 D0 01 12 08 00 5A FF           xxxxxxxxxxmm.
+"
+    );
+}
+
+#[test]
+fn hex_dump_marks() {
+    let mut coverage = Coverage::new(0x1000);
+    coverage.mark(0x200, 1, coverage::EXECUTED);
+    coverage.mark(0x201, 1, coverage::READ);
+    coverage.mark(0x202, 1, coverage::WRITTEN);
+    coverage.mark(0x203, 1, coverage::EXECUTED | coverage::WRITTEN);
+    coverage.mark(0x204, 1, coverage::READ | coverage::WRITTEN);
+    coverage.mark(0x210, 1, coverage::READ);
+
+    let mut out = vec![];
+    coverage.write_hexdump(&[0; 0x1000], 5, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let rows: Vec<&str> = out.lines().skip(2).collect();
+    assert_eq!(
+        rows,
+        [
+            "0200  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00  xrwXm...........",
+            "0210  00                                               r",
+        ]
+    );
+}
@@ -0,0 +1,284 @@
+extern crate chip8;
+
+use std::io;
+use std::thread;
+
+use chip8::cpu::{
+    CpuError, ErrorPolicy, FaultAction, RomError, MAX_ROM_SIZE, PROGRAM_START, STACK_SIZE,
+};
+use chip8::frontend::Timing;
+use chip8::trace::{TraceFilter, Tracer};
+use chip8::{Platform, CPU};
+
+#[test]
+fn runs_a_rom_without_a_frontend() {
+    let mut cpu = CPU::new("roms/PONG2").unwrap();
+    for cycle in 0..1000 {
+        //the frontend's job: DXYN waits for a vblank on the default platform
+        if cycle % 8 == 0 {
+            cpu.end_frame();
+        }
+        cpu.emulate_cycle().unwrap();
+    }
+    assert!(cpu.gpu.gfx.iter().any(|&p| p != 0), "nothing was drawn");
+}
+
+#[test]
+fn roms_load_at_the_program_start() {
+    let cpu = CPU::from_bytes(&[0x12, 0x34]).unwrap();
+    let mem = cpu.mem.get_ref();
+    assert_eq!(&mem[PROGRAM_START..PROGRAM_START + 3], &[0x12, 0x34, 0x00]);
+    assert_eq!(&mem[..5], &[0xF0, 0x90, 0x90, 0x90, 0xF0], "font 0");
+    assert_eq!(cpu.pc, PROGRAM_START);
+
+    let cpu = CPU::from_reader(&[0x12, 0x34][..]).unwrap();
+    assert_eq!(
+        &cpu.mem.get_ref()[PROGRAM_START..PROGRAM_START + 2],
+        &[0x12, 0x34]
+    );
+}
+
+#[test]
+fn roms_must_fit_in_memory() {
+    assert!(CPU::from_bytes(&[0; MAX_ROM_SIZE]).is_ok());
+    match CPU::from_bytes(&[0; MAX_ROM_SIZE + 1]) {
+        Err(RomError::TooLarge { size, max }) => {
+            assert_eq!((size, max), (MAX_ROM_SIZE + 1, MAX_ROM_SIZE))
+        }
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+    match CPU::from_bytes(&[]) {
+        Err(RomError::Empty) => {}
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+    match CPU::new("roms/MISSING") {
+        Err(RomError::Io(ref e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn faults_are_typed() {
+    let cases: &[(&[u8], CpuError)] = &[
+        (
+            &[0x81, 0x28],
+            CpuError::InvalidOpcode {
+                pc: 0x200,
+                opcode: 0x8128,
+            },
+        ),
+        (
+            &[0x00, 0xEE],
+            CpuError::StackUnderflow {
+                pc: 0x200,
+                opcode: 0x00EE,
+            },
+        ),
+        (
+            &[0xAF, 0xFF, 0xF1, 0x65],
+            CpuError::IndexOutOfBounds {
+                pc: 0x202,
+                opcode: 0xF165,
+                address: 0x1000,
+            },
+        ),
+        (
+            &[0x60, 0x20, 0xE0, 0x9E],
+            CpuError::InvalidKey {
+                pc: 0x202,
+                opcode: 0xE09E,
+                key: 0x20,
+            },
+        ),
+    ];
+    for &(program, expected) in cases {
+        let mut cpu = CPU::from_bytes(program).unwrap();
+        let error = (0..2).map(|_| cpu.emulate_cycle()).find(Result::is_err);
+        assert_eq!(error, Some(Err(expected)));
+        assert_eq!(cpu.pc, expected.pc(), "halting leaves the pc on the fault");
+    }
+}
+
+#[test]
+fn calls_past_the_stack_size_overflow() {
+    //CALL 200
+    let mut cpu = CPU::from_bytes(&[0x22, 0x00]).unwrap();
+    for _ in 0..STACK_SIZE {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert_eq!(
+        cpu.emulate_cycle(),
+        Err(CpuError::StackOverflow {
+            pc: 0x200,
+            opcode: 0x2200
+        })
+    );
+}
+
+#[test]
+fn the_policy_chooses_what_a_fault_does() {
+    let fault = CpuError::InvalidOpcode {
+        pc: 0x200,
+        opcode: 0x8128,
+    };
+    for &(action, result) in &[
+        (FaultAction::Halt, Err(fault)),
+        (FaultAction::Skip, Err(fault)),
+        (FaultAction::Nop, Ok(())),
+    ] {
+        let mut cpu = CPU::from_bytes(&[0x81, 0x28]).unwrap();
+        cpu.error_policy = ErrorPolicy::all(FaultAction::Halt);
+        cpu.error_policy.invalid_opcode = action;
+        assert_eq!(cpu.emulate_cycle(), result, "{:?}", action);
+        let pc = if action == FaultAction::Halt {
+            0x200
+        } else {
+            0x202
+        };
+        assert_eq!(cpu.pc, pc, "{:?}", action);
+    }
+}
+
+#[test]
+fn faults_describe_themselves() {
+    let e = CpuError::IndexOutOfBounds {
+        pc: 0x202,
+        opcode: 0xF165,
+        address: 0x1000,
+    };
+    assert_eq!(
+        e.to_string(),
+        "I points outside memory (1000) at 202 (opcode F165)"
+    );
+    assert_eq!(
+        CpuError::PcOutOfBounds { pc: 0xFFF }.to_string(),
+        "program counter out of memory at FFF"
+    );
+}
+
+#[test]
+fn timers_count_down_to_zero() {
+    let mut cpu = CPU::from_bytes(&[0x12, 0x00]).unwrap();
+    cpu.delay_timer = 2;
+    cpu.sound_timer = 1;
+    cpu.tick_timers();
+    assert_eq!((cpu.delay_timer, cpu.sound_timer), (1, 0));
+    cpu.tick_timers();
+    cpu.tick_timers();
+    assert_eq!((cpu.delay_timer, cpu.sound_timer), (0, 0));
+}
+
+#[test]
+fn instructions_do_not_tick_timers() {
+    //JP 200
+    let mut cpu = CPU::from_bytes(&[0x12, 0x00]).unwrap();
+    cpu.delay_timer = 10;
+    for _ in 0..100 {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert_eq!(cpu.delay_timer, 10);
+
+    cpu.run_frame(Timing {
+        clock_hz: 6000,
+        timer_hz: 60,
+    })
+    .unwrap();
+    assert_eq!(
+        cpu.delay_timer, 9,
+        "one tick per frame however many instructions"
+    );
+    assert!(cpu.vblank);
+}
+
+#[test]
+fn skipping_an_out_of_bounds_fetch_moves_the_pc() {
+    //JP FFF leaves a single byte to fetch from
+    let mut cpu = CPU::from_bytes(&[0x1F, 0xFF]).unwrap();
+    cpu.error_policy = ErrorPolicy::all(FaultAction::Skip);
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.pc, 0xFFF);
+
+    assert_eq!(
+        cpu.emulate_cycle(),
+        Err(CpuError::PcOutOfBounds { pc: 0xFFF })
+    );
+    assert_eq!(cpu.pc, 0x001);
+}
+
+#[test]
+fn a_nop_out_of_bounds_fetch_does_not_loop() {
+    let mut cpu = CPU::from_bytes(&[0x1F, 0xFF]).unwrap();
+    cpu.error_policy = ErrorPolicy::all(FaultAction::Nop);
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.emulate_cycle(), Ok(()));
+    assert_ne!(cpu.pc, 0xFFF);
+}
+
+#[test]
+fn register_dumps_at_the_top_of_memory_wrap_i() {
+    //LD I, LONG FFFF; LD V0, 42; LD [I], V0; LD I, LONG FFFF; LD V0, [I]
+    let rom = [
+        0xF0, 0x00, 0xFF, 0xFF, 0x60, 0x42, 0xF0, 0x55, 0xF0, 0x00, 0xFF, 0xFF, 0x60, 0x00, 0xF0,
+        0x65,
+    ];
+    let mut cpu = CPU::with_platform(&rom, Platform::XoChip).unwrap();
+    for _ in 0..3 {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert_eq!(cpu.mem.get_ref()[0xFFFF], 0x42);
+    assert_eq!(cpu.index_reg, 0);
+
+    for _ in 0..3 {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert_eq!(cpu.registers[0], 0x42);
+    assert_eq!(cpu.index_reg, 0);
+}
+
+#[test]
+fn frames_tick_timers_at_timer_hz() {
+    //LD VF, 3C; LD DT, VF; JP 204
+    let mut cpu = CPU::from_bytes(&[0x6F, 0x3C, 0xFF, 0x15, 0x12, 0x04]).unwrap();
+    let timing = Timing {
+        clock_hz: 600,
+        timer_hz: 30,
+    };
+    cpu.run_frame(timing).unwrap();
+    assert_eq!((cpu.cycles, cpu.delay_timer), (10, 60));
+    cpu.run_frame(timing).unwrap();
+    assert_eq!((cpu.cycles, cpu.delay_timer), (20, 59));
+}
+
+#[test]
+fn frames_carry_on_past_skipped_faults() {
+    //LD V0, 05; LD DT, V0; an invalid opcode; JP 206
+    let mut cpu = CPU::from_bytes(&[0x60, 0x05, 0xF0, 0x15, 0xFF, 0xFF, 0x12, 0x06]).unwrap();
+    cpu.error_policy = ErrorPolicy::all(FaultAction::Skip);
+    let skipped = cpu
+        .run_frame(Timing {
+            clock_hz: 600,
+            timer_hz: 60,
+        })
+        .unwrap();
+    assert_eq!(
+        skipped,
+        vec![CpuError::InvalidOpcode {
+            pc: 0x204,
+            opcode: 0xFFFF
+        }]
+    );
+    assert_eq!((cpu.cycles, cpu.delay_timer), (10, 4));
+}
+
+#[test]
+fn a_traced_cpu_can_run_on_another_thread() {
+    let mut cpu = CPU::from_bytes(&[0x60, 0x42]).unwrap();
+    cpu.tracer = Some(Tracer::new(io::sink(), TraceFilter::default()));
+    let cpu = thread::spawn(move || {
+        cpu.emulate_cycle().unwrap();
+        cpu
+    })
+    .join()
+    .unwrap();
+    assert_eq!(cpu.registers[0], 0x42);
+}
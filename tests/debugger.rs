@@ -0,0 +1,182 @@
+extern crate chip8;
+
+use chip8::debugger::{Debugger, Outcome, Stop, StopAt};
+use chip8::frontend::Timing;
+use chip8::CPU;
+
+//LD V0, 5; loop: ADD V0, 1; CALL sub; JP loop; sub: RET
+const PROGRAM: &[u8] = &[0x60, 0x05, 0x70, 0x01, 0x22, 0x08, 0x12, 0x02, 0x00, 0xEE];
+
+fn output(result: Result<Outcome, String>) -> String {
+    match result {
+        Ok(Outcome::Output(text)) => text,
+        other => panic!("expected output, got {:?}", other),
+    }
+}
+
+fn debugging(program: &[u8]) -> (CPU, Debugger) {
+    let cpu = CPU::from_bytes(program).unwrap();
+    (cpu, Debugger::new(Timing::default()))
+}
+
+#[test]
+fn step() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    let text = output(debugger.execute(&mut cpu, "step"));
+    assert!(text.starts_with("0202: 7001"), "{}", text);
+    assert_eq!((cpu.pc, cpu.registers[0]), (0x202, 5));
+
+    output(debugger.execute(&mut cpu, "s 2"));
+    assert_eq!((cpu.pc, cpu.stack.len()), (0x208, 1));
+    assert_eq!(debugger.cycles, 3);
+}
+
+#[test]
+fn break_and_continue() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    output(debugger.execute(&mut cpu, "b 208"));
+    assert_eq!(output(debugger.execute(&mut cpu, "break")), "0208");
+
+    let text = output(debugger.execute(&mut cpu, "c"));
+    assert!(text.starts_with("breakpoint\n0208"), "{}", text);
+    assert_eq!(cpu.registers[0], 6);
+
+    //continuing from a breakpoint runs the loop once more before stopping
+    output(debugger.execute(&mut cpu, "continue"));
+    assert_eq!((cpu.pc, cpu.registers[0]), (0x208, 7));
+
+    output(debugger.execute(&mut cpu, "d 208"));
+    assert_eq!(output(debugger.execute(&mut cpu, "b")), "no breakpoints");
+    assert!(debugger.execute(&mut cpu, "d 208").is_err());
+}
+
+#[test]
+fn until() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    let text = output(debugger.execute(&mut cpu, "until 0x206"));
+    assert!(text.starts_with("0206: 1202"), "{}", text);
+    assert_eq!((cpu.pc, cpu.stack.len()), (0x206, 0));
+}
+
+#[test]
+fn faults_and_exits_stop_the_run() {
+    let (mut cpu, mut debugger) = debugging(&[0x00, 0xEE]);
+    let text = output(debugger.execute(&mut cpu, "c"));
+    assert!(
+        text.starts_with("fault: return with an empty stack"),
+        "{}",
+        text
+    );
+    assert_eq!(cpu.pc, 0x200);
+
+    let (mut cpu, mut debugger) = debugging(&[0x00, 0xFD]);
+    assert_eq!(output(debugger.execute(&mut cpu, "c")), "program exited");
+}
+
+#[test]
+fn timers_follow_the_instruction_count() {
+    //the default timing runs 500 instructions a second against 60 timer ticks
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    cpu.delay_timer = 100;
+    output(debugger.execute(&mut cpu, "s 1F4"));
+    assert_eq!(cpu.delay_timer, 40);
+}
+
+#[test]
+fn inspect_and_edit() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    output(debugger.execute(&mut cpu, "s 2"));
+    let regs = output(debugger.execute(&mut cpu, "regs"));
+    assert!(regs.starts_with("V0=06 V1=00"), "{}", regs);
+    assert!(regs.contains("PC=0204 I=0000"), "{}", regs);
+    assert!(regs.ends_with("stack=[]"), "{}", regs);
+
+    output(debugger.execute(&mut cpu, "w 300 AB CD"));
+    assert_eq!(
+        output(debugger.execute(&mut cpu, "x 300 3")),
+        "0300: AB CD 00"
+    );
+    assert_eq!(
+        output(debugger.execute(&mut cpu, "dump 200 12"))
+            .lines()
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn bad_commands_are_errors() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    assert!(debugger.execute(&mut cpu, "frobnicate").is_err());
+    assert!(debugger.execute(&mut cpu, "b xyz").is_err());
+    assert!(debugger.execute(&mut cpu, "b 0x0x208").is_err());
+    assert!(debugger.execute(&mut cpu, "until").is_err());
+    assert!(debugger.execute(&mut cpu, "w 300 1FF").is_err());
+    assert_eq!(debugger.execute(&mut cpu, "q"), Ok(Outcome::Quit));
+    assert_eq!(
+        debugger.execute(&mut cpu, "  "),
+        Ok(Outcome::Output(String::new()))
+    );
+}
+
+#[test]
+fn run_stops_where_asked() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    match debugger.run(&mut cpu, 100, Some(StopAt::Address(0x208))) {
+        Stop::Reached => assert_eq!(cpu.stack.len(), 1),
+        stop => panic!("{:?}", stop),
+    }
+
+    //back out of the call: the stack is no deeper than it was before it
+    match debugger.run(&mut cpu, 100, Some(StopAt::Depth(0))) {
+        Stop::Reached => assert_eq!(cpu.pc, 0x206),
+        stop => panic!("{:?}", stop),
+    }
+
+    match debugger.run(&mut cpu, 3, None) {
+        Stop::Done => assert_eq!(cpu.pc, 0x208),
+        stop => panic!("{:?}", stop),
+    }
+}
+
+#[test]
+fn run_stops_at_breakpoints_unless_resuming() {
+    let (mut cpu, mut debugger) = debugging(PROGRAM);
+    debugger.breakpoints.insert(0x202);
+    match debugger.run(&mut cpu, 100, None) {
+        Stop::Breakpoint => assert_eq!(cpu.pc, 0x202),
+        stop => panic!("{:?}", stop),
+    }
+    match debugger.run(&mut cpu, 100, None) {
+        Stop::Breakpoint => assert_eq!(cpu.registers[0], 5, "no progress"),
+        stop => panic!("{:?}", stop),
+    }
+    debugger.resume();
+    match debugger.run(&mut cpu, 100, None) {
+        Stop::Breakpoint => assert_eq!(cpu.registers[0], 6),
+        stop => panic!("{:?}", stop),
+    }
+
+    let (mut cpu, mut debugger) = debugging(&[0x00, 0xEE]);
+    match debugger.run(&mut cpu, 100, None) {
+        Stop::Fault(e) => assert_eq!(e.pc(), 0x200),
+        stop => panic!("{:?}", stop),
+    }
+}
+
+#[test]
+fn dumps_stop_at_the_end_of_memory() {
+    let (mut cpu, mut debugger) = debugging(&[0x12, 0x00]);
+    let text = output(debugger.execute(&mut cpu, "x FF0 FFFFFFFFFFFFFFFF"));
+    assert_eq!(text.lines().count(), 1);
+    assert!(text.starts_with("0FF0: 00"));
+}
+
+#[test]
+fn writes_past_the_end_of_memory_are_refused() {
+    let (mut cpu, mut debugger) = debugging(&[0x12, 0x00]);
+    assert!(debugger.execute(&mut cpu, "w FFFFFFFFFFFFFFFF 01").is_err());
+    assert!(debugger.execute(&mut cpu, "w FFF 01 02").is_err());
+    output(debugger.execute(&mut cpu, "w FFF 01"));
+    assert_eq!(cpu.mem.get_ref()[0xFFF], 0x01);
+}
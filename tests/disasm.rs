@@ -0,0 +1,76 @@
+extern crate chip8;
+
+use chip8::disasm;
+use chip8::Instructions;
+
+#[test]
+fn listings_label_jump_and_call_targets() {
+    //LD V0, 5; loop: ADD V0, 1; CALL sub; JP loop; sub: RET; data; odd byte
+    let rom = [
+        0x60, 0x05, 0x70, 0x01, 0x22, 0x08, 0x12, 0x02, 0x00, 0xEE, 0xFF, 0xFF, 0xAB,
+    ];
+    let listing = disasm::disassemble(&rom);
+    assert_eq!(
+        listing.to_string(),
+        "    200: 6005       LD V0, 0x05
+label_202:
+    202: 7001       ADD V0, 0x01
+    204: 2208       CALL sub_208
+    206: 1202       JP label_202
+sub_208:
+    208: 00EE       RET
+    20A: FFFF       DW 0xFFFF
+    20C: AB         DB 0xAB
+"
+    );
+}
+
+#[test]
+fn long_i_covers_its_operand() {
+    let listing = disasm::disassemble(&[0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0xF0, 0x00]);
+    let lines: Vec<(usize, String)> = listing
+        .lines
+        .iter()
+        .map(|l| (l.address, l.mnemonic(&listing.labels)))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (0x200, "LD I, LONG 0x1234".to_string()),
+            (0x204, "CLS".to_string()),
+            (0x206, "DW 0xF000".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn targets_inside_an_instruction_get_no_label() {
+    //JP 203 lands in the middle of a word
+    let listing = disasm::disassemble(&[0x12, 0x03, 0x00, 0xE0]);
+    assert!(listing.labels.is_empty());
+    assert_eq!(listing.lines[0].mnemonic(&listing.labels), "JP 0x203");
+}
+
+#[test]
+fn mnemonics() {
+    let cases = [
+        (
+            Instructions::SetValueToReg { x: 3, value: 0x1F },
+            "LD V3, 0x1F",
+        ),
+        (
+            Instructions::Display {
+                x: 1,
+                y: 2,
+                value: 0,
+            },
+            "DRW V1, V2, 0",
+        ),
+        (Instructions::RegDump { x: 0xA }, "LD [I], VA"),
+        (Instructions::ScrollDown(4), "SCD 4"),
+        (Instructions::SelectPlane(3), "PLANE 3"),
+    ];
+    for &(instruction, text) in &cases {
+        assert_eq!(instruction.to_string(), text);
+    }
+}
@@ -0,0 +1,71 @@
+extern crate chip8;
+
+use std::time::{Duration, Instant};
+
+use chip8::audio::Beeper;
+use chip8::frontend::{self, qwerty_keypad, Audio, Command, FrameRecorder, Input, Timing};
+use chip8::CPU;
+
+/// Holds key 5 down, then quits after `after`.
+struct QuitAfter {
+    start: Instant,
+    after: Duration,
+}
+
+impl Input for QuitAfter {
+    fn poll(&mut self, keys: &mut [u8; 16]) -> Vec<Command> {
+        keys[5] = 1;
+        if self.start.elapsed() >= self.after {
+            vec![Command::Quit]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[derive(Default)]
+struct CountingAudio {
+    updates: usize,
+}
+
+impl Audio for CountingAudio {
+    fn update(&mut self, _beeper: &Beeper) {
+        self.updates += 1;
+    }
+}
+
+#[test]
+fn run_drives_every_backend_until_quit() {
+    let mut cpu = CPU::new("roms/PONG2").unwrap();
+    let mut display = FrameRecorder::default();
+    let mut input = QuitAfter {
+        start: Instant::now(),
+        after: Duration::from_millis(100),
+    };
+    let mut audio = CountingAudio::default();
+    frontend::run(
+        &mut cpu,
+        Timing::default(),
+        &mut display,
+        &mut input,
+        &mut audio,
+    )
+    .unwrap();
+
+    assert!(!display.frames.is_empty());
+    assert_eq!(display.frames.len(), audio.updates);
+    assert!(display.frames.iter().all(|f| f.len() == 64 * 32));
+    assert_eq!(cpu.keys[5], 1);
+}
+
+#[test]
+fn qwerty_keypad_reaches_every_key_once() {
+    let mut keys: Vec<usize> = "1234qwerasdfzxcv"
+        .chars()
+        .filter_map(qwerty_keypad)
+        .collect();
+    keys.sort();
+    assert_eq!(keys, (0..16).collect::<Vec<_>>());
+    assert_eq!(qwerty_keypad('Q'), Some(0x4));
+    assert_eq!(qwerty_keypad('5'), None);
+}
@@ -0,0 +1,117 @@
+extern crate chip8;
+
+use std::convert::TryFrom;
+
+use chip8::instructions::{DecodeError, EncodeError};
+use chip8::Instructions;
+
+#[test]
+fn invalid_words_are_errors() {
+    for &opcode in &[0x0123, 0x5121, 0x8128, 0x9AB1, 0xE1FF, 0xFFFF] {
+        assert_eq!(
+            Instructions::try_from(opcode),
+            Err(DecodeError {
+                opcode,
+                address: None
+            })
+        );
+    }
+}
+
+#[test]
+fn decode_reports_the_address() {
+    let e = Instructions::decode(0x8128, 0x2A4).unwrap_err();
+    assert_eq!(e.address, Some(0x2A4));
+    assert_eq!(e.to_string(), "invalid opcode 8128 at 2A4");
+
+    let e = Instructions::try_from(0x8128).unwrap_err();
+    assert_eq!(e.to_string(), "invalid opcode 8128");
+}
+
+#[test]
+fn valid_words_decode_the_same_either_way() {
+    for &opcode in &[0x00E0, 0x1234, 0x5120, 0x8126, 0xD015, 0xF165] {
+        assert_eq!(
+            Instructions::decode(opcode, 0x200),
+            Instructions::try_from(opcode)
+        );
+        assert!(Instructions::try_from(opcode).is_ok());
+    }
+}
+
+#[test]
+fn every_decodable_word_encodes_back() {
+    for opcode in 0..=0xFFFF_u16 {
+        if let Ok(instruction) = Instructions::try_from(opcode) {
+            assert_eq!(
+                instruction.encode(),
+                Ok(opcode),
+                "{:04X} decodes to {:?}",
+                opcode,
+                instruction
+            );
+        }
+    }
+}
+
+#[test]
+fn decodable_words_cover_the_instruction_set() {
+    let decodable = (0..=0xFFFF_u16)
+        .filter(|op| Instructions::try_from(*op).is_ok())
+        .count();
+    //0NNN: 16 SCD + 16 SCU + 7 fixed; 1-4, 6, 7, A-D: 4096 each;
+    //5XY0/2/3 and 8XY0-7/E: 256 per variant, 9XY0: 256; EX9E, EXA1: 16 each;
+    //F000, F002, FN01 (16) and 13 FX variants of 16
+    let expected = 39 + 10 * 4096 + 3 * 256 + 9 * 256 + 256 + 32 + 2 + 16 + 13 * 16;
+    assert_eq!(decodable, expected);
+}
+
+#[test]
+fn wide_addresses_are_rejected() {
+    assert_eq!(
+        Instructions::JumpToAddress(0x1000).encode(),
+        Err(EncodeError::Address(0x1000))
+    );
+    assert_eq!(
+        Instructions::SetMem { value: 0xFFFF }.encode(),
+        Err(EncodeError::Address(0xFFFF))
+    );
+    assert_eq!(Instructions::CallSub(0xFFF).encode(), Ok(0x2FFF));
+}
+
+#[test]
+fn wide_registers_are_rejected() {
+    assert_eq!(
+        Instructions::AssignAddValue { x: 16, y: 1 }.encode(),
+        Err(EncodeError::Register(16))
+    );
+    assert_eq!(
+        Instructions::AssignAddValue { x: 1, y: 16 }.encode(),
+        Err(EncodeError::Register(16))
+    );
+    assert_eq!(
+        Instructions::RegLoad { x: 0xFF }.encode(),
+        Err(EncodeError::Register(0xFF))
+    );
+}
+
+#[test]
+fn wide_nibbles_are_rejected() {
+    assert_eq!(
+        Instructions::Display {
+            x: 0,
+            y: 1,
+            value: 16
+        }
+        .encode(),
+        Err(EncodeError::Nibble(16))
+    );
+    assert_eq!(
+        Instructions::ScrollDown(16).encode(),
+        Err(EncodeError::Nibble(16))
+    );
+    assert_eq!(
+        Instructions::SelectPlane(16).encode(),
+        Err(EncodeError::Nibble(16))
+    );
+}
@@ -0,0 +1,216 @@
+extern crate chip8;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use chip8::cpu::{ErrorPolicy, FaultAction};
+use chip8::frontend::{Command, Event, Input, NullAudio, NullDisplay, Runner, Timing};
+use chip8::movie::{Movie, MovieError, Playback, VERSION};
+use chip8::rng::Rng;
+use chip8::CPU;
+
+const UFO: &[u8] = include_bytes!("../roms/UFO");
+
+/// Keys held during `frame` of the recordings here.
+fn keys_for(frame: usize) -> [u8; 16] {
+    let mut keys = [0; 16];
+    keys[frame / 8 % 16] = 1;
+    keys
+}
+
+/// 60 frames of UFO with a fixed seed and changing keys.
+fn recording() -> Movie {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    cpu.rng = Rng::new(3);
+    let mut movie = Movie::new(UFO, &cpu, Timing::default());
+    for frame in 0..60 {
+        cpu.keys = keys_for(frame);
+        cpu.run_frame(Timing::default()).unwrap();
+        movie.record(&cpu.keys, &cpu.gpu);
+    }
+    movie
+}
+
+/// Plays `movie` from power-on, returning the frames `verify` rejected.
+fn play(movie: Movie) -> (Playback, Vec<usize>) {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    cpu.rng = movie.rng();
+    let mut playback = Playback::new(movie);
+    let mut rejected = vec![];
+    while !playback.finished() {
+        let frame = playback.frame;
+        playback.feed(&mut cpu.keys);
+        assert_eq!(cpu.keys, keys_for(frame));
+        cpu.run_frame(Timing::default()).unwrap();
+        if !playback.verify(&cpu.gpu) {
+            rejected.push(frame);
+        }
+    }
+    (playback, rejected)
+}
+
+#[test]
+fn movies_round_trip_through_files() {
+    let movie = recording();
+    assert_eq!(movie.frames.len(), 60);
+    assert!(movie.matches_rom(UFO));
+    assert!(!movie.matches_rom(&UFO[1..]));
+
+    let mut file = vec![];
+    movie.write(&mut file).unwrap();
+    assert_eq!(Movie::read(&file[..]).unwrap(), movie);
+}
+
+#[test]
+fn movies_keep_the_fault_policy() {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    cpu.error_policy = ErrorPolicy {
+        invalid_opcode: FaultAction::Nop,
+        invalid_key: FaultAction::Skip,
+        ..ErrorPolicy::default()
+    };
+    let movie = Movie::new(UFO, &cpu, Timing::default());
+    assert_eq!(movie.error_policy, cpu.error_policy);
+
+    let mut file = vec![];
+    movie.write(&mut file).unwrap();
+    assert_eq!(
+        Movie::read(&file[..]).unwrap().error_policy,
+        cpu.error_policy
+    );
+
+    //the policy is the six bytes before the frame count
+    let invalid_key = file.len() - 5;
+    assert_eq!(file[invalid_key], 1);
+    file[invalid_key] = 3;
+    match Movie::read(&file[..]) {
+        Err(MovieError::Corrupt(what)) => assert_eq!(what, "fault policy"),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn playback_reproduces_the_recording() {
+    let (playback, rejected) = play(recording());
+    assert!(rejected.is_empty());
+    assert_eq!((playback.frame, playback.desync), (60, None));
+}
+
+#[test]
+fn playback_reports_the_first_desync_only() {
+    let mut movie = recording();
+    movie.frames[20].checksum ^= 1;
+    movie.frames[40].checksum ^= 1;
+    let (playback, rejected) = play(movie);
+    assert_eq!(rejected, [20]);
+    assert_eq!(playback.desync, Some(20));
+}
+
+#[test]
+fn other_files_are_refused() {
+    match Movie::read(&b"C8SS\x00\x01"[..]) {
+        Err(MovieError::BadMagic) => {}
+        other => panic!("{:?}", other),
+    }
+
+    let mut file = vec![];
+    recording().write(&mut file).unwrap();
+    file[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+    match Movie::read(&file[..]) {
+        Err(MovieError::UnsupportedVersion(v)) => assert_eq!(v, VERSION + 1),
+        other => panic!("{:?}", other),
+    }
+}
+
+/// Presses `keys_for` each frame, sends `command` at frame `command_at` and
+/// quits after `frames`.
+struct Script {
+    frame: usize,
+    frames: usize,
+    command_at: Option<(usize, Command)>,
+}
+
+impl Input for Script {
+    fn poll(&mut self, keys: &mut [u8; 16]) -> Vec<Command> {
+        let frame = self.frame;
+        self.frame += 1;
+        *keys = keys_for(frame);
+        match self.command_at {
+            _ if frame == self.frames => vec![Command::Quit],
+            Some((at, command)) if at == frame => vec![command],
+            _ => vec![],
+        }
+    }
+}
+
+/// Runs UFO under a `Runner` set up by `setup` for up to `frames`,
+/// returning the events it reported.
+fn run<F: FnOnce(&mut Runner, &mut CPU)>(
+    frames: usize,
+    command_at: Option<(usize, Command)>,
+    setup: F,
+) -> (Runner, Vec<String>) {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    cpu.rng = Rng::new(3);
+    let mut runner = Runner::new(Timing::default());
+    setup(&mut runner, &mut cpu);
+    let events = Rc::new(RefCell::new(vec![]));
+    let sink = events.clone();
+    runner.on_event = Some(Box::new(move |e: Event| {
+        sink.borrow_mut().push(e.to_string())
+    }));
+    let mut input = Script {
+        frame: 0,
+        frames,
+        command_at,
+    };
+    runner
+        .run(&mut cpu, &mut NullDisplay, &mut input, &mut NullAudio)
+        .unwrap();
+    let events = events.borrow().clone();
+    (runner, events)
+}
+
+/// 20 frames of UFO recorded by a `Runner`.
+fn runner_recording() -> Movie {
+    let (mut runner, events) = run(20, None, |runner, cpu| {
+        runner.recording = Some(Movie::new(UFO, cpu, Timing::default()));
+    });
+    assert!(events.is_empty());
+    runner.recording.take().unwrap()
+}
+
+fn play_in_runner(movie: Movie) -> Vec<String> {
+    let (_, events) = run(100, None, |runner, cpu| {
+        cpu.rng = movie.rng();
+        runner.playback = Some(Playback::new(movie));
+    });
+    events
+}
+
+#[test]
+fn runners_report_the_end_of_a_movie() {
+    let movie = runner_recording();
+    assert_eq!(movie.frames.len(), 20);
+    assert_eq!(play_in_runner(movie), ["movie ended after 20 frames"]);
+}
+
+#[test]
+fn runners_report_the_first_desync() {
+    let mut movie = runner_recording();
+    movie.frames[5].checksum ^= 1;
+    movie.frames[9].checksum ^= 1;
+    assert_eq!(
+        play_in_runner(movie),
+        ["movie desync at frame 5", "movie ended after 20 frames"]
+    );
+}
+
+#[test]
+fn runners_refuse_loads_during_a_movie() {
+    let (runner, events) = run(5, Some((2, Command::LoadState(1))), |runner, cpu| {
+        runner.recording = Some(Movie::new(UFO, cpu, Timing::default()));
+    });
+    assert_eq!(events, ["cannot load or rewind during a movie"]);
+    assert_eq!(runner.recording.unwrap().frames.len(), 5);
+}
@@ -0,0 +1,192 @@
+extern crate chip8;
+
+use chip8::frontend::{Timing, FRAME_RATE};
+use chip8::profile::{HotAddress, ProfileFormat, Profiler, Report};
+use chip8::CPU;
+
+//LD V0, 1; ADD V0, 1; ADD V1, 2; JP 0x202
+const PROGRAM: [u8; 8] = [0x60, 0x01, 0x70, 0x01, 0x71, 0x02, 0x12, 0x02];
+
+/// Timing that runs `n` instructions and ticks the timers once per frame.
+fn instructions(n: u32) -> Timing {
+    Timing {
+        clock_hz: n * FRAME_RATE,
+        timer_hz: FRAME_RATE,
+    }
+}
+
+/// The report after running PROGRAM for frames of `per_frame` instructions.
+fn profile(per_frame: &[u32], top: usize) -> Report {
+    let mut cpu = CPU::from_bytes(&PROGRAM).unwrap();
+    cpu.profiler = Some(Profiler::new(cpu.mem.get_ref().len()));
+    for &n in per_frame {
+        cpu.run_frame(instructions(n)).unwrap();
+    }
+    let profiler = cpu.profiler.take().unwrap();
+    profiler.report(cpu.mem.get_ref(), top)
+}
+
+#[test]
+fn counts_by_address_and_kind() {
+    let report = profile(&[4, 6], 10);
+    assert_eq!(report.total, 10);
+    let addresses: Vec<(usize, u64)> = report
+        .addresses
+        .iter()
+        .map(|a| (a.address, a.count))
+        .collect();
+    assert_eq!(addresses, [(0x202, 3), (0x204, 3), (0x206, 3), (0x200, 1)]);
+    assert_eq!(report.addresses[0].mnemonic, "ADD V0, 0x01");
+    assert_eq!(
+        report.kinds,
+        [
+            ("AddValueToReg".to_string(), 6),
+            ("JumpToAddress".to_string(), 3),
+            ("SetValueToReg".to_string(), 1),
+        ]
+    );
+}
+
+#[test]
+fn frames_are_summarised() {
+    let report = profile(&[4, 0, 6, 5], 10);
+    assert_eq!(report.frames, 3, "the empty frame is left out");
+    assert_eq!((report.min_per_frame, report.max_per_frame), (4, 6));
+    assert_eq!(report.mean_per_frame, 5.0);
+
+    let report = profile(&[], 10);
+    assert_eq!(
+        (report.total, report.frames, report.mean_per_frame),
+        (0, 0, 0.0)
+    );
+    assert!(report.addresses.is_empty());
+}
+
+#[test]
+fn top_keeps_the_hottest_addresses() {
+    let report = profile(&[4, 6], 2);
+    let addresses: Vec<usize> = report.addresses.iter().map(|a| a.address).collect();
+    assert_eq!(addresses, [0x202, 0x204], "ties go to the lower address");
+}
+
+#[test]
+fn text_report() {
+    assert_eq!(
+        profile(&[4, 4], 2).to_string(),
+        "8 instructions over 2 frames, 4.0 per frame (min 4, max 4)
+
+hot addresses:
+  0202           3   37.5%  ADD V0, 0x01
+  0204           2   25.0%  ADD V1, 0x02
+
+instructions by kind:
+  AddValueToReg                  5   62.5%
+  JumpToAddress                  2   25.0%
+  SetValueToReg                  1   12.5%
+"
+    );
+}
+
+#[test]
+fn json_report() {
+    assert_eq!(
+        profile(&[4, 3], 1).to_json(),
+        "{\"instructions\":7,\"frames\":2,\"per_frame\":{\"min\":3,\"mean\":3.50,\"max\":4},\
+         \"addresses\":[{\"address\":514,\"count\":2,\"instruction\":\"ADD V0, 0x01\"}],\
+         \"kinds\":{\"AddValueToReg\":4,\"JumpToAddress\":2,\"SetValueToReg\":1}}"
+    );
+}
+
+#[test]
+fn json_strings_are_escaped() {
+    let report = Report {
+        total: 1,
+        frames: 0,
+        min_per_frame: 0,
+        max_per_frame: 0,
+        mean_per_frame: 0.0,
+        addresses: vec![HotAddress {
+            address: 0,
+            count: 1,
+            mnemonic: "DB \"a\\b\"".to_string(),
+        }],
+        kinds: vec![],
+    };
+    assert!(report
+        .to_json()
+        .contains("\"instruction\":\"DB \\\"a\\\\b\\\"\""));
+}
+
+#[test]
+fn formats_parse() {
+    assert_eq!("text".parse(), Ok(ProfileFormat::Text));
+    assert_eq!("json".parse(), Ok(ProfileFormat::Json));
+    assert_eq!(
+        "xml".parse::<ProfileFormat>(),
+        Err("unknown profile format xml".to_string())
+    );
+}
+
+//CALL 0x206; JP 0x202; CALL 0x20C; RET; LD V0, 1; RET
+const CALLS: [u8; 16] = [
+    0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x22, 0x0C, 0x00, 0xEE, 0x00, 0x00, 0x60, 0x01, 0x00, 0xEE,
+];
+
+/// A profiler that has watched CALLS for 8 instructions: two in each
+/// subroutine and four in main.
+fn calls() -> Profiler {
+    let mut cpu = CPU::from_bytes(&CALLS).unwrap();
+    cpu.profiler = Some(Profiler::new(cpu.mem.get_ref().len()));
+    cpu.run_frame(instructions(8)).unwrap();
+    cpu.profiler.take().unwrap()
+}
+
+#[test]
+fn folded_stacks() {
+    let mut out = vec![];
+    calls().write_folded(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "main 4\nmain;sub_206 2\nmain;sub_206;sub_20C 2\n"
+    );
+}
+
+#[test]
+fn dot_call_graph() {
+    let mut out = vec![];
+    calls().write_dot(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"digraph calls {
+    node [shape=box];
+    "main" [label="main\nself 50.0%\ntotal 100.0%"];
+    "sub_206" [label="sub_206\nself 25.0%\ntotal 50.0%"];
+    "sub_20C" [label="sub_20C\nself 25.0%\ntotal 25.0%"];
+    "main" -> "sub_206" [label="1"];
+    "sub_206" -> "sub_20C" [label="1"];
+}
+"#
+    );
+}
+
+#[test]
+fn recursion_counts_once_per_stack() {
+    //CALL 0x200
+    let mut cpu = CPU::from_bytes(&[0x22, 0x00]).unwrap();
+    cpu.profiler = Some(Profiler::new(cpu.mem.get_ref().len()));
+    cpu.run_frame(instructions(3)).unwrap();
+    let profiler = cpu.profiler.take().unwrap();
+
+    let mut folded = vec![];
+    profiler.write_folded(&mut folded).unwrap();
+    assert_eq!(
+        String::from_utf8(folded).unwrap(),
+        "main 1\nmain;sub_200 1\nmain;sub_200;sub_200 1\n"
+    );
+
+    let mut dot = vec![];
+    profiler.write_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("\"sub_200\" [label=\"sub_200\\nself 66.7%\\ntotal 66.7%\"];"));
+    assert!(dot.contains("\"sub_200\" -> \"sub_200\" [label=\"2\"];"));
+}
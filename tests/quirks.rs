@@ -0,0 +1,123 @@
+extern crate chip8;
+
+use chip8::{Platform, Quirks, CPU};
+
+/// A CPU loaded with `rom` that behaves like CHIP-48, except for whatever
+/// `quirk` changes.
+fn cpu_with<F: Fn(&mut Quirks)>(rom: &[u8], quirk: F) -> CPU {
+    let mut cpu = CPU::from_bytes(rom).unwrap();
+    cpu.quirks = Quirks::chip48();
+    quirk(&mut cpu.quirks);
+    cpu
+}
+
+#[test]
+fn shift_source() {
+    //SHR V0, V1
+    for &(uses_vy, expected) in &[(false, 0x04), (true, 0x20)] {
+        let mut cpu = cpu_with(&[0x80, 0x16], |q| q.shift_uses_vy = uses_vy);
+        cpu.registers[0x0] = 0x08;
+        cpu.registers[0x1] = 0x41;
+        cpu.emulate_cycle().unwrap();
+        assert_eq!(cpu.registers[0x0], expected, "shift_uses_vy {}", uses_vy);
+        assert_eq!(cpu.registers[0xF], if uses_vy { 1 } else { 0 });
+    }
+}
+
+#[test]
+fn load_store_increment() {
+    //LD [I], V2; LD V2, [I]
+    for &(increments, expected) in &[(false, 0x300), (true, 0x306)] {
+        let mut cpu = cpu_with(&[0xF2, 0x55, 0xF2, 0x65], |q| {
+            q.load_store_increments_i = increments
+        });
+        cpu.index_reg = 0x300;
+        cpu.emulate_cycle().unwrap();
+        cpu.emulate_cycle().unwrap();
+        assert_eq!(
+            cpu.index_reg, expected,
+            "load_store_increments_i {}",
+            increments
+        );
+    }
+}
+
+#[test]
+fn jump_offset() {
+    //JP V0, 0x310
+    for &(uses_vx, expected) in &[(false, 0x311), (true, 0x313)] {
+        let mut cpu = cpu_with(&[0xB3, 0x10], |q| q.jump_uses_vx = uses_vx);
+        cpu.registers[0x0] = 0x01;
+        cpu.registers[0x3] = 0x03;
+        cpu.emulate_cycle().unwrap();
+        assert_eq!(cpu.pc, expected, "jump_uses_vx {}", uses_vx);
+    }
+}
+
+#[test]
+fn clipping() {
+    //DRW V0, V1, 1 with I at the 8-pixel row that follows it
+    for &wrap in &[false, true] {
+        let mut cpu = cpu_with(&[0xD0, 0x11, 0xFF], |q| q.sprite_wrap = wrap);
+        cpu.index_reg = 0x202;
+        cpu.registers[0x0] = 60;
+        cpu.emulate_cycle().unwrap();
+        assert_eq!(cpu.gpu.pixel(63, 0), 1);
+        for x in 0..4 {
+            assert_eq!(cpu.gpu.pixel(x, 0) == 1, wrap, "sprite_wrap {}", wrap);
+        }
+    }
+}
+
+#[test]
+fn vf_reset() {
+    //OR V1, V2; AND V1, V2; XOR V1, V2
+    for &op in &[0x21, 0x22, 0x23] {
+        for &(resets, expected) in &[(false, 0x05), (true, 0x00)] {
+            let mut cpu = cpu_with(&[0x81, op], |q| q.logic_resets_vf = resets);
+            cpu.registers[0xF] = 0x05;
+            cpu.emulate_cycle().unwrap();
+            assert_eq!(cpu.registers[0xF], expected, "81{:02X}", op);
+        }
+    }
+}
+
+#[test]
+fn display_wait() {
+    //DRW V0, V0, 1 with I at the row that follows it
+    let mut cpu = cpu_with(&[0xD0, 0x01, 0x80], |q| q.display_wait = true);
+    cpu.index_reg = 0x202;
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(
+        (cpu.pc, cpu.gpu.pixel(0, 0)),
+        (0x200, 0),
+        "waits for vblank"
+    );
+
+    cpu.vblank = true;
+    cpu.emulate_cycle().unwrap();
+    assert_eq!((cpu.pc, cpu.gpu.pixel(0, 0)), (0x202, 1));
+    assert!(!cpu.vblank, "the draw used up the vblank");
+
+    let mut cpu = cpu_with(&[0xD0, 0x01, 0x80], |q| q.display_wait = false);
+    cpu.index_reg = 0x202;
+    cpu.emulate_cycle().unwrap();
+    assert_eq!((cpu.pc, cpu.gpu.pixel(0, 0)), (0x202, 1));
+}
+
+#[test]
+fn platforms_parse_and_pick_their_quirks() {
+    for &platform in &[
+        Platform::CosmacVip,
+        Platform::Chip48,
+        Platform::SuperChip,
+        Platform::XoChip,
+    ] {
+        assert_eq!(platform.to_string().parse(), Ok(platform));
+    }
+    assert_eq!("chip8".parse(), Ok(Platform::CosmacVip));
+    assert!("nes".parse::<Platform>().is_err());
+
+    assert_eq!(Platform::default().quirks(), Quirks::cosmac_vip());
+    assert_eq!(Platform::SuperChip.quirks(), Quirks::chip48());
+}
@@ -0,0 +1,62 @@
+extern crate chip8;
+
+use chip8::frontend::Timing;
+use chip8::rewind::RewindBuffer;
+use chip8::CPU;
+
+const UFO: &[u8] = include_bytes!("../roms/UFO");
+
+/// Runs UFO for `frames` frames, recording each into `history`. Returns the
+/// state after every frame.
+fn record(cpu: &mut CPU, history: &mut RewindBuffer, frames: usize) -> Vec<Vec<u8>> {
+    (0..frames)
+        .map(|_| {
+            cpu.run_frame(Timing::default()).unwrap();
+            history.push(cpu);
+            cpu.state_bytes()
+        })
+        .collect()
+}
+
+#[test]
+fn rewinding_restores_earlier_frames_in_order() {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    let mut history = RewindBuffer::new(100);
+    let states = record(&mut cpu, &mut history, 40);
+    assert_eq!(history.len(), 39);
+
+    for n in 1..=25 {
+        assert!(history.step_back(&mut cpu));
+        assert_eq!(
+            cpu.state_bytes(),
+            states[states.len() - 1 - n],
+            "frame -{}",
+            n
+        );
+    }
+    assert_eq!(history.len(), 14);
+}
+
+#[test]
+fn rewinding_stops_at_the_capacity() {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    let mut history = RewindBuffer::new(10);
+    let states = record(&mut cpu, &mut history, 30);
+
+    for _ in 0..10 {
+        assert!(history.step_back(&mut cpu));
+    }
+    assert!(!history.step_back(&mut cpu));
+    assert_eq!(cpu.state_bytes(), states[states.len() - 11]);
+}
+
+#[test]
+fn rewinding_keeps_the_held_keys() {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    let mut history = RewindBuffer::new(10);
+    record(&mut cpu, &mut history, 5);
+
+    cpu.keys[4] = 1;
+    assert!(history.step_back(&mut cpu));
+    assert_eq!(cpu.keys[4], 1);
+}
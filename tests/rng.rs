@@ -0,0 +1,71 @@
+extern crate chip8;
+
+use chip8::frontend::Timing;
+use chip8::rng::Rng;
+use chip8::CPU;
+
+const UFO: &[u8] = include_bytes!("../roms/UFO");
+
+#[test]
+fn xorshift_sequence() {
+    let mut rng = Rng::new(42);
+    let bytes: Vec<u8> = (0..5).map(|_| rng.next_u8()).collect();
+    assert_eq!(bytes, [0x00, 0xA9, 0x1C, 0xD9, 0xE0]);
+}
+
+#[test]
+fn a_zero_seed_still_generates() {
+    let mut rng = Rng::new(0);
+    assert!((0..8).map(|_| rng.next_u8()).any(|b| b != 0));
+}
+
+#[test]
+fn seeded_generators_repeat() {
+    let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+    for _ in 0..100 {
+        assert_eq!(a.next_u8(), b.next_u8());
+    }
+    let first = |seed| {
+        let mut rng = Rng::new(seed);
+        (0..4).map(|_| rng.next_u8()).collect::<Vec<_>>()
+    };
+    assert_ne!(first(42), first(43));
+}
+
+#[test]
+fn seeded_cpus_draw_the_same_numbers() {
+    //RND V0, FF; RND V1, FF; JP 200
+    let program = [0xC0, 0xFF, 0xC1, 0xFF, 0x12, 0x00];
+    let (mut a, mut b) = (
+        CPU::from_bytes(&program).unwrap(),
+        CPU::from_bytes(&program).unwrap(),
+    );
+    a.rng = Rng::new(7);
+    b.rng = Rng::new(7);
+    let mut seen = vec![];
+    for _ in 0..30 {
+        a.emulate_cycle().unwrap();
+        b.emulate_cycle().unwrap();
+        assert_eq!(a.registers, b.registers);
+        seen.push(a.registers[0]);
+    }
+    assert!(seen.iter().any(|&v| v != seen[0]));
+}
+
+#[test]
+fn save_states_carry_the_generator() {
+    let mut cpu = CPU::from_bytes(UFO).unwrap();
+    cpu.rng = Rng::new(1);
+    for _ in 0..90 {
+        cpu.run_frame(Timing::default()).unwrap();
+    }
+
+    let mut restored = CPU::from_bytes(UFO).unwrap();
+    restored.load_state(&cpu.state_bytes()[..]).unwrap();
+    assert_eq!(restored.rng, cpu.rng);
+    for _ in 0..60 {
+        cpu.run_frame(Timing::default()).unwrap();
+        restored.run_frame(Timing::default()).unwrap();
+    }
+    assert_eq!(restored.state_bytes(), cpu.state_bytes());
+}
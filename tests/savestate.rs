@@ -0,0 +1,106 @@
+extern crate chip8;
+
+use std::env;
+use std::fs;
+
+use chip8::frontend::Timing;
+use chip8::savestate::{SaveSlots, StateError, VERSION};
+use chip8::CPU;
+
+//ADD V0, 1; LD DT, V0; CALL 208; JP 200; RET
+const PROGRAM: &[u8] = &[0x70, 0x01, 0xF0, 0x15, 0x22, 0x08, 0x12, 0x00, 0x00, 0xEE];
+
+//9 instructions a frame, so two frames stop just after the CALL
+const TIMING: Timing = Timing {
+    clock_hz: 540,
+    timer_hz: 60,
+};
+
+/// `PROGRAM` stopped inside its subroutine with timers running.
+fn running_cpu() -> CPU {
+    let mut cpu = CPU::from_bytes(PROGRAM).unwrap();
+    cpu.run_frame(TIMING).unwrap();
+    cpu.run_frame(TIMING).unwrap();
+    assert_eq!(cpu.stack.len(), 1);
+    cpu
+}
+
+#[test]
+fn loading_a_saved_state_restores_the_machine() {
+    let mut cpu = running_cpu();
+    let saved = cpu.state_bytes();
+
+    let mut restored = CPU::from_bytes(PROGRAM).unwrap();
+    restored.load_state(&saved[..]).unwrap();
+    assert_eq!(restored.state_bytes(), saved);
+    assert_eq!((restored.pc, &restored.stack), (cpu.pc, &cpu.stack));
+    assert_eq!(restored.registers, cpu.registers);
+
+    //both carry on identically
+    for _ in 0..10 {
+        cpu.run_frame(TIMING).unwrap();
+        restored.run_frame(TIMING).unwrap();
+    }
+    assert_eq!(restored.state_bytes(), cpu.state_bytes());
+}
+
+#[test]
+fn other_files_are_refused() {
+    let mut cpu = CPU::from_bytes(PROGRAM).unwrap();
+    match cpu.load_state(&b"RIFF\x00\x01"[..]) {
+        Err(StateError::BadMagic) => {}
+        other => panic!("{:?}", other),
+    }
+    let state = running_cpu().state_bytes();
+    match cpu.load_state(&state[..state.len() / 2]) {
+        Err(StateError::Io(_)) => {}
+        other => panic!("loaded half a state: {:?}", other),
+    }
+}
+
+#[test]
+fn newer_versions_are_refused() {
+    let mut state = running_cpu().state_bytes();
+    state[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+
+    let mut cpu = CPU::from_bytes(PROGRAM).unwrap();
+    let before = cpu.state_bytes();
+    match cpu.load_state(&state[..]) {
+        Err(StateError::UnsupportedVersion(v)) => assert_eq!(v, VERSION + 1),
+        other => panic!("loaded a newer state: {:?}", other),
+    }
+    assert_eq!(cpu.state_bytes(), before);
+}
+
+#[test]
+fn slots_are_files_next_to_the_rom() {
+    let dir = env::temp_dir().join(format!("chip8-savestate-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let slots = SaveSlots::for_rom(dir.join("GAME"));
+    assert_eq!(slots.path(3), dir.join("GAME.state3"));
+
+    let cpu = running_cpu();
+    slots.save(&cpu, 3).unwrap();
+    let mut restored = CPU::from_bytes(PROGRAM).unwrap();
+    slots.load(&mut restored, 3).unwrap();
+    assert_eq!(restored.state_bytes(), cpu.state_bytes());
+    assert!(slots.load(&mut restored, 4).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn return_addresses_outside_memory_are_refused() {
+    let mut state = running_cpu().state_bytes();
+    //the one stack entry follows the header, pc, I, V0-VF, timers and depth
+    assert_eq!(state[32], 1);
+    state[33..37].copy_from_slice(&0x1_0000_u32.to_be_bytes());
+
+    let mut cpu = CPU::from_bytes(PROGRAM).unwrap();
+    let before = cpu.state_bytes();
+    match cpu.load_state(&state[..]) {
+        Err(StateError::Corrupt(what)) => assert_eq!(what, "stack"),
+        other => panic!("loaded a bad return address: {:?}", other),
+    }
+    assert_eq!(cpu.state_bytes(), before);
+}
@@ -0,0 +1,86 @@
+extern crate chip8;
+
+use chip8::cpu::BIG_FONT_START;
+use chip8::{Quirks, CPU};
+
+fn superchip(program: &[u8]) -> CPU {
+    let mut cpu = CPU::from_bytes(program).unwrap();
+    cpu.quirks = Quirks::superchip();
+    cpu
+}
+
+fn run(cpu: &mut CPU, cycles: usize) {
+    for _ in 0..cycles {
+        cpu.emulate_cycle().unwrap();
+    }
+}
+
+#[test]
+fn hires_dxy0_draws_a_16x16_sprite() {
+    //HIGH; DRW V0, V1, 0; LOW
+    let mut program = vec![0x00, 0xFF, 0xD0, 0x10, 0x00, 0xFE];
+    program.extend_from_slice(&[0xFF; 32]);
+    let mut cpu = superchip(&program);
+    cpu.index_reg = 0x206;
+    run(&mut cpu, 2);
+
+    assert_eq!((cpu.gpu.width(), cpu.gpu.height()), (128, 64));
+    assert_eq!(cpu.gpu.pixel(15, 15), 1);
+    assert_eq!(cpu.gpu.pixel(16, 0), 0);
+    assert_eq!(cpu.gpu.pixel(0, 16), 0);
+    assert_eq!(cpu.registers[0xF], 0);
+
+    run(&mut cpu, 1);
+    assert_eq!((cpu.gpu.width(), cpu.gpu.height()), (64, 32));
+}
+
+#[test]
+fn scrolling() {
+    //SCD 2; SCR; SCL
+    let mut cpu = superchip(&[0x00, 0xC2, 0x00, 0xFB, 0x00, 0xFC]);
+    cpu.gpu.draw(0, 0, &[0x80], false, false);
+
+    run(&mut cpu, 1);
+    assert_eq!((cpu.gpu.pixel(0, 0), cpu.gpu.pixel(0, 2)), (0, 1));
+    run(&mut cpu, 1);
+    assert_eq!((cpu.gpu.pixel(0, 2), cpu.gpu.pixel(4, 2)), (0, 1));
+    run(&mut cpu, 1);
+    assert_eq!((cpu.gpu.pixel(4, 2), cpu.gpu.pixel(0, 2)), (0, 1));
+}
+
+#[test]
+fn exit_halts() {
+    let mut cpu = superchip(&[0x00, 0xFD]);
+    run(&mut cpu, 2);
+    assert!(cpu.halted);
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn big_digits() {
+    //LD HF, V3
+    let mut cpu = superchip(&[0xF3, 0x30]);
+    cpu.registers[3] = 7;
+    run(&mut cpu, 1);
+    let i = cpu.index_reg as usize;
+    assert_eq!(i, BIG_FONT_START + 70);
+    assert_eq!(&cpu.mem.get_ref()[i..i + 4], &[0xFF, 0xFF, 0x03, 0x03]);
+}
+
+#[test]
+fn rpl_flags_round_trip() {
+    //LD R, V2; LD V0, 0 x3; LD V2, R
+    let mut cpu = superchip(&[0xF2, 0x75, 0x60, 0x00, 0x61, 0x00, 0x62, 0x00, 0xF2, 0x85]);
+    cpu.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+    run(&mut cpu, 5);
+    assert_eq!(&cpu.rpl[..4], &[1, 2, 3, 0]);
+    assert_eq!(&cpu.registers[..4], &[1, 2, 3, 4]);
+}
+
+#[test]
+fn other_platforms_run_superchip_instructions_too() {
+    //HIGH on a COSMAC VIP
+    let mut cpu = CPU::from_bytes(&[0x00, 0xFF]).unwrap();
+    run(&mut cpu, 1);
+    assert_eq!((cpu.gpu.width(), cpu.gpu.height()), (128, 64));
+}
@@ -0,0 +1,293 @@
+extern crate chip8;
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use chip8::trace::{self, Record, TraceError, TraceFilter, Tracer};
+use chip8::CPU;
+
+//LD I, 0x2EA; LD V3, 0x42; DRW V0, V0, 1; JP 0x206
+const PROGRAM: [u8; 8] = [0xA2, 0xEA, 0x63, 0x42, 0xD0, 0x01, 0x12, 0x06];
+
+/// A writer the test can read back after the CPU owns the tracer.
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Shared {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+fn trace(filter: TraceFilter, cycles: usize) -> Vec<String> {
+    let out = Shared::default();
+    let mut cpu = CPU::from_bytes(&PROGRAM).unwrap();
+    cpu.quirks.display_wait = false;
+    cpu.tracer = Some(Tracer::new(out.clone(), filter));
+    for _ in 0..cycles {
+        cpu.emulate_cycle().unwrap();
+    }
+    cpu.tracer.take().unwrap().finish().unwrap();
+    out.lines()
+}
+
+#[test]
+fn lines_have_a_fixed_format() {
+    let lines = trace(TraceFilter::default(), 3);
+    assert_eq!(
+        lines,
+        [
+            "00000001 0200 A2EA LD I, 0x2EA          V=00000000000000000000000000000000 I=0000 SP=0 DT=00 ST=00 W=",
+            "00000002 0202 6342 LD V3, 0x42          V=00000000000000000000000000000000 I=02EA SP=0 DT=00 ST=00 W=",
+            "00000003 0204 D001 DRW V0, V0, 1        V=00000042000000000000000000000000 I=02EA SP=0 DT=00 ST=00 W=",
+        ]
+    );
+}
+
+#[test]
+fn filters_pick_addresses_and_mnemonics() {
+    let by_range = TraceFilter {
+        ranges: vec![(0x202, 0x204)],
+        ..TraceFilter::default()
+    };
+    let pcs: Vec<String> = trace(by_range, 4)
+        .iter()
+        .map(|l| l[9..13].to_string())
+        .collect();
+    assert_eq!(pcs, ["0202", "0204"]);
+
+    let by_mnemonic = TraceFilter {
+        mnemonics: vec!["jp".to_string()],
+        ..TraceFilter::default()
+    };
+    let cycles: Vec<String> = trace(by_mnemonic, 6)
+        .iter()
+        .map(|l| l[..8].to_string())
+        .collect();
+    assert_eq!(cycles, ["00000004", "00000005", "00000006"]);
+}
+
+#[test]
+fn filters_need_both_the_range_and_the_mnemonic() {
+    let filter = TraceFilter {
+        ranges: vec![(0x200, 0x20F), (0x300, 0x300)],
+        mnemonics: vec!["DRW".to_string(), "call".to_string()],
+    };
+    assert!(filter.in_range(0x20F) && filter.in_range(0x300));
+    assert!(!filter.in_range(0x210) && !filter.in_range(0x1FF));
+    assert!(filter.of_kind("CALL 0x300") && filter.of_kind("drw V0, V1, 5"));
+    assert!(!filter.of_kind("CALLX") && !filter.of_kind(""));
+    assert!(filter.matches(0x204, "DRW V0, V1, 5"));
+    assert!(!filter.matches(0x210, "DRW V0, V1, 5"));
+    assert!(!filter.matches(0x204, "CLS"));
+}
+
+#[test]
+fn dropping_the_tracer_writes_the_last_line() {
+    let out = Shared::default();
+    let mut cpu = CPU::from_bytes(&PROGRAM).unwrap();
+    cpu.tracer = Some(Tracer::new(out.clone(), TraceFilter::default()));
+    cpu.emulate_cycle().unwrap();
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(out.lines().len(), 1, "the second line waits for its writes");
+    drop(cpu);
+    assert_eq!(out.lines().len(), 2);
+}
+
+#[test]
+fn write_errors_are_reported_by_finish() {
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut cpu = CPU::from_bytes(&PROGRAM).unwrap();
+    cpu.tracer = Some(Tracer::new(Broken, TraceFilter::default()));
+    cpu.emulate_cycle().unwrap();
+    cpu.emulate_cycle().unwrap();
+    let e = cpu.tracer.take().unwrap().finish().unwrap_err();
+    assert_eq!(e.to_string(), "disk full");
+}
+
+#[test]
+fn lines_end_with_the_memory_written() {
+    //LD V2, 0x80; LD I, 0x400; LD B, V2; LD [I], V1
+    let rom = [0x62, 0x80, 0xA4, 0x00, 0xF2, 0x33, 0xF1, 0x55];
+    let out = Shared::default();
+    let mut cpu = CPU::from_bytes(&rom).unwrap();
+    cpu.tracer = Some(Tracer::new(out.clone(), TraceFilter::default()));
+    for _ in 0..4 {
+        cpu.emulate_cycle().unwrap();
+    }
+    cpu.tracer.take().unwrap().finish().unwrap();
+    let writes: Vec<String> = out
+        .lines()
+        .iter()
+        .map(|l| l[l.find(" W=").unwrap() + 3..].to_string())
+        .collect();
+    assert_eq!(
+        writes,
+        ["", "", "0400:01,0401:02,0402:08", "0400:00,0401:00"]
+    );
+}
+
+#[test]
+fn records_parse_our_own_lines() {
+    let line = "00000107 0310 F233 LD B, V2             V=80000000000000000000000000000001 I=0400 SP=1 DT=3C ST=00 W=0400:01,0401:02,0402:08";
+    let record = Record::parse(line).unwrap();
+    let mut registers = [0; 16];
+    registers[0x0] = 0x80;
+    registers[0xF] = 0x01;
+    assert_eq!(
+        record,
+        Record {
+            text: line.to_string(),
+            cycle: 107,
+            pc: 0x310,
+            opcode: 0xF233,
+            registers: Some(registers),
+            index: Some(0x400),
+            sp: Some(1),
+            delay_timer: Some(0x3C),
+            sound_timer: Some(0),
+            writes: Some(vec![(0x400, 0x01), (0x401, 0x02), (0x402, 0x08)]),
+        }
+    );
+}
+
+#[test]
+fn records_parse_foreign_lines() {
+    let record = Record::parse("1 0x200 A2EA foo I=0 V=00000000000000000000000000000000").unwrap();
+    assert_eq!((record.cycle, record.pc, record.opcode), (1, 0x200, 0xA2EA));
+    assert_eq!(record.registers, Some([0; 16]));
+    assert_eq!(record.index, Some(0));
+    assert_eq!(
+        (record.sp, record.delay_timer, record.writes),
+        (None, None, None)
+    );
+
+    assert_eq!(Record::parse("1 200").unwrap_err(), "missing opcode");
+    assert_eq!(Record::parse("x 200 A2EA").unwrap_err(), "bad cycle x");
+    assert_eq!(Record::parse("1 20G A2EA").unwrap_err(), "bad hex 20G");
+    assert_eq!(
+        Record::parse("1 200 A2EA V=00").unwrap_err(),
+        "V= needs 32 hex digits, got 00"
+    );
+    assert_eq!(
+        Record::parse("1 200 A2EA W=0400").unwrap_err(),
+        "bad write 0400, expected ADDR:BYTE"
+    );
+}
+
+#[test]
+fn reading_skips_comments_and_names_bad_lines() {
+    let text = "# header\n\n1 200 00E0\n2 202 zz\n";
+    let read: Vec<_> = trace::records(text.as_bytes()).collect();
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[0].as_ref().unwrap().opcode, 0x00E0);
+    assert_eq!(
+        read[1].as_ref().unwrap_err().to_string(),
+        "line 4: bad hex zz"
+    );
+}
+
+/// Records for the lines of `text`.
+fn records(text: &str) -> Vec<Result<Record, TraceError>> {
+    trace::records(text.as_bytes()).collect()
+}
+
+const A: &str = "1 200 6001 V=00000000000000000000000000000000 I=0 DT=05
+2 202 6102 V=01000000000000000000000000000000 I=0 DT=04
+3 204 A300 V=01020000000000000000000000000000 I=0 DT=03
+4 206 7001 V=01020000000000000000000000000000 I=300 DT=02
+5 208 1208 V=02020000000000000000000000000000 I=300 DT=01
+";
+
+#[test]
+fn identical_traces_do_not_diverge() {
+    assert_eq!(trace::diff(records(A), records(A), 3).unwrap(), None);
+
+    //timers and fields only one side has are not compared
+    let other = A
+        .replace("DT=0", "DT=1")
+        .replace(" I=0 ", " ")
+        .replace(" I=300 ", " ");
+    assert_eq!(trace::diff(records(A), records(&other), 3).unwrap(), None);
+}
+
+#[test]
+fn the_first_divergence_is_reported() {
+    let b = A
+        .replace("3 204 A300 V=0102", "3 204 A300 V=0103")
+        .replace("4 206 7001 V=0102", "4 206 7001 V=0104")
+        .replace("I=300", "I=301");
+    let divergence = trace::diff(records(A), records(&b), 0).unwrap().unwrap();
+    assert_eq!(divergence.record, 2);
+    assert_eq!(divergence.fields, ["V1"]);
+    assert!(divergence.before.is_empty());
+    assert_eq!(divergence.after.0.len(), 1);
+    assert_eq!(divergence.after.1[0].text, b.lines().nth(2).unwrap());
+    assert!(divergence
+        .to_string()
+        .starts_with("traces diverge at record 3, cycle 3: V1\n"));
+}
+
+#[test]
+fn context_surrounds_the_divergence() {
+    let b = A.replace("4 206 7001", "4 206 7002");
+    let divergence = trace::diff(records(A), records(&b), 2).unwrap().unwrap();
+    let cycles = |records: &[Record]| records.iter().map(|r| r.cycle).collect::<Vec<_>>();
+    assert_eq!(divergence.record, 3);
+    assert_eq!(divergence.fields, ["opcode"]);
+    assert_eq!(cycles(&divergence.before), [2, 3]);
+    assert_eq!(cycles(&divergence.after.0), [4, 5]);
+    assert_eq!(cycles(&divergence.after.1), [4, 5]);
+
+    let lines: Vec<&str> = A.lines().collect();
+    assert_eq!(
+        divergence.to_string(),
+        format!(
+            "traces diverge at record 4, cycle 4: opcode\n  {}\n  {}\na {}\nb {}\na {}\nb {}\n",
+            lines[1],
+            lines[2],
+            lines[3],
+            b.lines().nth(3).unwrap(),
+            lines[4],
+            lines[4]
+        )
+    );
+}
+
+#[test]
+fn a_shorter_trace_diverges_where_it_ends() {
+    let short: String = A.lines().take(3).map(|l| format!("{}\n", l)).collect();
+    let divergence = trace::diff(records(A), records(&short), 1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(divergence.record, 3);
+    assert!(divergence.fields.is_empty());
+    assert!(divergence
+        .to_string()
+        .starts_with("the second trace ends after 3 records\n"));
+}
@@ -0,0 +1,84 @@
+extern crate chip8;
+
+use chip8::cpu::{DEFAULT_PITCH, PROGRAM_START};
+use chip8::{Platform, CPU};
+
+fn xochip(program: &[u8]) -> CPU {
+    CPU::with_platform(program, Platform::XoChip).unwrap()
+}
+
+fn run(cpu: &mut CPU, cycles: usize) {
+    for _ in 0..cycles {
+        cpu.emulate_cycle().unwrap();
+    }
+}
+
+#[test]
+fn memory_is_64k() {
+    let cpu = xochip(&[0; 0x8000]);
+    assert_eq!(cpu.mem.get_ref().len(), 0x10000);
+    assert!(CPU::with_platform(&[0; 0x8000], Platform::SuperChip).is_err());
+}
+
+#[test]
+fn long_i() {
+    //LD I, LONG 0xBEEF; then SE V0, 0 skips a whole F000 NNNN
+    let mut cpu = xochip(&[0xF0, 0x00, 0xBE, 0xEF, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34]);
+    run(&mut cpu, 1);
+    assert_eq!((cpu.index_reg, cpu.pc), (0xBEEF, PROGRAM_START + 4));
+    run(&mut cpu, 1);
+    assert_eq!(cpu.pc, PROGRAM_START + 10);
+}
+
+#[test]
+fn register_ranges() {
+    //SAVE V1 - V3; LOAD V3 - V1 (reversed, so the values swap ends)
+    let mut cpu = xochip(&[0x51, 0x32, 0x53, 0x13]);
+    cpu.index_reg = 0x300;
+    cpu.registers[1..4].copy_from_slice(&[1, 2, 3]);
+    run(&mut cpu, 1);
+    assert_eq!(&cpu.mem.get_ref()[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(cpu.index_reg, 0x300, "I is left alone");
+    run(&mut cpu, 1);
+    assert_eq!(&cpu.registers[1..4], &[3, 2, 1]);
+}
+
+#[test]
+fn plane_selection() {
+    //PLANE 2; DRW V0, V0, 1; PLANE 3; DRW V0, V1, 1
+    let mut cpu = xochip(&[0xF2, 0x01, 0xD0, 0x01, 0xF3, 0x01, 0xD0, 0x11, 0xC0, 0x80]);
+    cpu.index_reg = 0x208;
+    cpu.registers[1] = 1;
+    run(&mut cpu, 2);
+    assert_eq!((cpu.gpu.pixel(0, 0), cpu.gpu.pixel(1, 0)), (2, 2));
+
+    //with both planes selected the sprite holds one row per plane, plane 1 first
+    run(&mut cpu, 2);
+    assert_eq!((cpu.gpu.pixel(0, 1), cpu.gpu.pixel(1, 1)), (3, 1));
+}
+
+#[test]
+fn scroll_up() {
+    //SCU 3
+    let mut cpu = xochip(&[0x00, 0xD3]);
+    cpu.gpu.draw(0, 5, &[0x80], false, false);
+    run(&mut cpu, 1);
+    assert_eq!((cpu.gpu.pixel(0, 5), cpu.gpu.pixel(0, 2)), (0, 1));
+}
+
+#[test]
+fn audio_pattern_and_pitch() {
+    //AUDIO; PITCH V5
+    let mut program = vec![0xF0, 0x02, 0xF5, 0x3A];
+    program.extend(0..16);
+    let mut cpu = xochip(&program);
+    cpu.index_reg = 0x204;
+    cpu.registers[5] = 112;
+    assert_eq!((cpu.audio_pattern, cpu.pitch), (None, DEFAULT_PITCH));
+    run(&mut cpu, 2);
+
+    let mut expected = [0; 16];
+    (0..16).for_each(|n| expected[n] = n as u8);
+    assert_eq!(cpu.audio_pattern, Some(expected));
+    assert_eq!(cpu.pitch, 112);
+}